@@ -4,7 +4,143 @@ view = View
 welcome = Welcome to COSMIC! ✨
 page-id = Page { $num }
 git-description = Git commit {$hash} on {$date}
-switcher-text = Different wallpapers for Light and Dark modes
+switcher-dark = Manage Dark mode wallpapers
+switcher-light = Manage Light mode wallpapers
 settings-dark = Dark mode wallpapers...
 settings-light = Light mode wallpapers...
+settings = Wallpaper settings...
+applet-settings = Applet settings...
+save-snapshot = Save look...
+load-snapshot = Apply look...
+reset-settings = Reset all settings
+reset-confirm = Reset all settings?
+reset = Reset
+cancel = Cancel
+cant-save = Settings can't be saved
+config-errors = { $count ->
+    [one] 1 config problem
+    *[other] {$count} config problems
+}
+close = Close
+setting-extract-colors = Extract colors from wallpapers
+setting-use-bg-color = Use the wallpaper color itself for color wallpapers (images are still analyzed)
+setting-shade-count = Shades of solid colors: {$count}
+setting-include-base-color = Include the solid color itself
+setting-include-gradient-colors = Include gradient colors
+setting-include-gradient-average = Mixes of adjacent gradient colors
+setting-color-metric = Color comparison
+metric-cielab = Perceptual (CIELAB)
+metric-rgb = RGB
+setting-swatch-sort = Swatch order
+setting-min-luminance = Leave out swatches darker than {$luminance}
+setting-max-luminance = Leave out swatches lighter than {$luminance}
+sort-frequency = Most common first
+sort-luminance = Light to dark
+sort-hue = By hue
+setting-quantize = Merge swatches equal once quantized (off: only identical ones)
+setting-quantize-bits = Bits per component: { $bits }
+setting-deterministic = Stable color extraction
+setting-merge-distance = Merge swatches closer than {$distance}× a noticeable difference
+setting-log-timings = Log how long color extraction takes
+setting-color-threshold = Custom color threshold
+setting-color-threshold-value = Colors covering at least {$percent}% of the wallpaper
+setting-high-contrast = High contrast accent color
+setting-gtk-accent = Apply the accent color to GTK applications
+setting-quality = Color extraction quality
+quality-low = Fast
+quality-medium = Balanced
+quality-high = Accurate
+setting-animate-accent = Fade between accents
+setting-auto-accent = Pick the accent color automatically
+setting-remember-accent = Remember the accent picked for each wallpaper
+setting-temperature-bias = Preferred accent colors
+bias-none = Any
+bias-warm = Warm
+bias-cool = Cool
+setting-pinned-hue = Keep the accent close to a hue
+setting-backend = Apply wallpapers through
+backend-cosmic-bg = COSMIC background
+backend-portal = Desktop portal
+setting-scope = Apply wallpapers
+scope-follow-system = As captured
+scope-shared = Shared by all outputs
+scope-per-output = Per output
+setting-enforce = Restore wallpapers changed by other apps
+setting-slideshow = Follow slideshows
+setting-slideshow-secs = Check every {$secs} s
+next-slideshow-check = Slideshow checked in {$time}
+next-switch-dark = Dark mode in {$time}
+next-switch-light = Light mode in {$time}
+pause-automation = Pause automation
+focus-mode = Focus mode
+automation-paused = Automation paused, click to resume
+automation-paused-until = Automation paused, resumes in {$time}
+setting-pause-resume = Resume paused automation by itself
+setting-pause-resume-hours = Resume after {$hours} h
+setting-schedule = Switch modes on a schedule
+setting-schedule-mode = Follow
+schedule-manual = Set times
+schedule-solar = Sunrise and sunset
+setting-latitude = Latitude: {$degrees}°
+setting-longitude = Longitude: {$degrees}°
+solar-fallback = The times below are used on days the sun doesn't rise or set.
+setting-dark-start = Dark mode from {$time}
+setting-light-start = Light mode from {$time}
+setting-dark-command = Dark mode settings command
+setting-light-command = Light mode settings command
+setting-icon-name = Panel icon
+solid-color = Use a solid color...
+recent-colors = Recent colors
+copy-to-clipboard = Copy to clipboard
+copied-to-clipboard = Copied to clipboard
+hex = Hex
+rgb = RGB
+link-modes = Same fit for wallpapers used in both modes
+preview = Preview on the desktop
+undo-preview = Undo the preview
+fit = Fit
+fit-zoom = Fill screen
+fit-fit = Fit to screen
+fit-stretch = Stretch
+no-wallpapers = No wallpapers captured for this mode yet. Open the settings to pick them.
 accent-color = Accent color
+manage-theme = Manage the accent color
+blend = Blend with current
+saturation = Saturation
+lightness = Lightness
+random-accent = Random accent color
+current-accent = Current accent
+revert-accent = Undo accent changes
+import-palette = Import palette...
+imported-palette = Imported
+apply-suggested-accent = Apply suggested accent
+dismiss = Dismiss
+show-wallpapers = Show wallpapers
+hide-wallpapers = Hide wallpapers
+remove = Remove
+open-gallery = Browse wallpapers...
+close-gallery = Close gallery
+gallery-loading = Extracting colors of {$count} more images...
+more-swatches = +{$count} more
+color-count = Colors per wallpaper: {$count}
+fewer-swatches = Show fewer
+accent-target = Apply to
+mode-dark = Dark mode
+mode-light = Light mode
+vision = Preview as seen with
+vision-normal = Typical color vision
+vision-protanopia = Protanopia
+vision-deuteranopia = Deuteranopia
+vision-tritanopia = Tritanopia
+swatches-set-window-hint = Swatches set the window hint
+window-hint-follows-accent = Use the accent for the window hint
+copy-palette = Copy palette
+window-hint = Tint windows with the accent color
+setting-separate-window-hint = Pick the window hint separately (advanced)
+history = Recent palettes
+source-color = Color
+source-gradient = Gradient
+outputs = Displays
+same-on-all = Same wallpaper on all displays
+accent-source = Accent colors from {$output}
+all-outputs = All displays
@@ -1,31 +1,57 @@
 // SPDX-License-Identifier: GPL-3.0
 
 use std::borrow::Cow;
-use std::path::PathBuf;
+use std::collections::{BTreeSet, HashMap};
+use std::f32::consts::FRAC_PI_2;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::LazyLock;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::config::{Bg, Config};
+mod settings;
+
+use ashpd::desktop::file_chooser::SelectedFiles;
+use chrono::{DateTime, Local};
+
+use crate::cache::ColorCache;
+use crate::colors;
+use crate::config::{bg_context, load_bg_config, logged_write, wait_for_bg_config, Bg, Config};
 use crate::fl;
+use crate::gtk;
+use crate::snapshot::Snapshot;
 use crate::unique::UniqueIterator;
 use cosmic::applet::token::subscription::{
     activation_token_subscription, TokenRequest, TokenUpdate,
 };
 use cosmic::applet::{menu_button, padded_control};
 use cosmic::cctk::sctk::reexports::calloop;
+use cosmic::cctk::wayland_client::protocol::wl_output::WlOutput;
 use cosmic::cosmic_config::{self, CosmicConfigEntry};
-use cosmic::cosmic_theme::palette::{Darken, Lighten, Mix, Srgb};
-use cosmic::cosmic_theme::{Theme, ThemeBuilder, ThemeMode};
-use cosmic::iced::{color, Alignment, Color, Length};
-use cosmic::iced::{window::Id, Subscription};
+use cosmic::cosmic_theme::palette::{Mix, Srgb};
+use cosmic::cosmic_theme::{
+    Theme, ThemeBuilder, ThemeMode, DARK_THEME_BUILDER_ID, LIGHT_THEME_BUILDER_ID,
+};
+use cosmic::iced::event::{self, wayland, PlatformSpecific};
+use cosmic::iced::gradient::Linear;
+use cosmic::iced::{window::Id, Event, Subscription};
+use cosmic::iced::{Alignment, Background, Border, Color, Length, Limits, Radians};
 use cosmic::iced_core::text::Wrapping;
+use cosmic::iced_widget::scrollable::{self, AbsoluteOffset};
 use cosmic::iced_widget::{column, row};
 use cosmic::iced_winit::commands::popup::{destroy_popup, get_popup};
-use cosmic::iced_winit::graphics::image::image_rs::Pixel;
+use cosmic::iced_winit::graphics::image::image_rs::{ImageFormat, Pixel, RgbaImage};
 use cosmic::prelude::*;
-use cosmic::widget::color_picker::color_button;
+use cosmic::widget::color_picker::{color_button, ColorPickerModel, ColorPickerUpdate};
 use cosmic::widget::settings::item_row;
-use cosmic::widget::{container, divider, text, toggler};
-use cosmic_bg_config::{context, Context, Source};
+use cosmic::widget::{
+    button, container, divider, dropdown, image, slider, text, toggler, tooltip, Space,
+};
+use cosmic::Application;
+use cosmic_bg_config::{context, Context, Entry, ScalingMode, Source};
 use cosmic_settings_wallpaper::load_image_with_thumbnail;
+use settings::{Setting, TextSetting};
 
 #[derive(Default)]
 pub struct AppModel {
@@ -33,308 +59,2456 @@ pub struct AppModel {
     popup: Option<Id>,
     config_handler: Option<cosmic_config::Config>,
     config: Config,
+    /// Whether the last attempt to save the config failed.
+    save_failed: bool,
+    /// The problems the watched configs were loaded with, until dismissed.
+    config_errors: Vec<String>,
+    errors_expanded: bool,
     token_tx: Option<calloop::channel::Sender<TokenRequest>>,
     colors: Vec<Color>,
+    /// The overall color of the wallpapers `colors` were extracted from.
+    average: Option<Color>,
+    /// The accent picked by hand, which re-applying wallpapers must never override.
+    accent: Option<Color>,
+    /// The color picked for the window hint, which otherwise follows the accent. The theme has
+    /// no secondary accent, so the window hint is where a second color goes.
+    secondary: Option<Color>,
+    /// The accent the theme of the current mode was last seen with, to tell the applet's own
+    /// writes from changes made elsewhere.
+    theme_accent: Option<Color>,
+    /// Human-friendly names of the connected outputs, keyed by their connector name.
+    outputs: HashMap<String, String>,
+    /// The connector names of the connected outputs.
+    connected: HashMap<WlOutput, String>,
+    /// A short description of the wallpapers `colors` were extracted from.
+    label: String,
+    history_expanded: bool,
+    /// Whether the swatches past the configured count are shown.
+    swatches_expanded: bool,
+    /// The configured icon name if such an icon exists, the default one otherwise.
+    icon_name: String,
+    /// A description of the current default background, shown as the panel icon's tooltip.
+    wallpaper: String,
+    /// The saturation and lightness shifts applied on top of the picked accent.
+    adjustment: (f32, f32),
+    /// How much of the current accent is kept when a swatch is picked.
+    blend: f32,
+    /// Where the popup was scrolled to when it was last closed.
+    scroll_offset: AbsoluteOffset,
+    /// The backgrounds cosmic-bg currently shows.
+    bg: Bg,
+    outputs_expanded: bool,
+    /// The labels of the wallpaper fit dropdowns.
+    scaling_modes: Vec<String>,
+    /// The labels of the accent target dropdown.
+    modes: Vec<String>,
+    /// The labels of the color vision dropdown.
+    visions: Vec<String>,
+    /// The color vision deficiency the swatches are previewed under, if any.
+    vision: Option<colors::ColorVision>,
+    /// The mode picked accents are applied to, if not the current one.
+    accent_target: Option<bool>,
+    /// Counts the accent animations, so only the steps of the latest one are written.
+    accent_animation: u64,
+    /// The intermediate accents the latest animation wrote, which aren't external changes.
+    animation_writes: Vec<Color>,
+    /// When the wallpaper settings were last launched from the applet.
+    settings_launched: Option<Instant>,
+    /// When the schedule next switches modes, computed again on the first check after it's
+    /// passed or the schedule changed.
+    next_switch: Option<DateTime<Local>>,
+    /// Counts the swatch recomputations, so only the extraction of the latest one is shown.
+    extraction_generation: u64,
+    /// The theme builder and theme configs of the light and dark mode, opened on the first accent
+    /// write and reused after.
+    theme_configs: [Option<(cosmic_config::Config, cosmic_config::Config)>; 2],
+    /// The accent and window hint the theme of the light and dark mode had before the applet
+    /// first wrote them, for undoing its changes.
+    original_theme: [Option<(Option<Srgb>, Option<Srgb>)>; 2],
+    /// When the shown wallpapers were last checked for a slideshow change.
+    last_poll: Option<Instant>,
+    /// Whether the slideshow checks and the wallpaper enforcement are suspended.
+    paused: bool,
+    /// When paused automation resumes by itself, if it does.
+    resume_at: Option<Instant>,
+    /// Counts the pauses, so only the auto-resume of the latest one applies.
+    pauses: u64,
+    /// Counts the theme mode changes, so only the last of several quick ones applies its
+    /// wallpapers.
+    mode_switches: u64,
+    rng: fastrand::Rng,
+    cache: ColorCache,
+    settings_popup: Option<Id>,
+    /// The mode whose wallpapers are listed with a thumbnail each, if any.
+    entries_expanded: Option<bool>,
+    /// The thumbnails of the listed image wallpapers.
+    thumbnails: HashMap<PathBuf, image::Handle>,
+    /// The backgrounds and accent to restore once focus mode is left.
+    focus: Option<Snapshot>,
+    /// The backgrounds to restore once the preview of a mode's wallpapers is undone.
+    preview: Option<Vec<Entry>>,
+    /// The sources `colors` were extracted from.
+    sources: Vec<Source>,
+    /// Whether the settings popup asks to confirm resetting all settings.
+    confirm_reset: bool,
+    /// Accent candidates imported from a color-scheme file, shown until dismissed.
+    imported: Vec<Color>,
+    /// The images of the directory picked for the gallery along with their thumbnails and
+    /// colors, until closed.
+    gallery: Vec<(PathBuf, image::Handle, Vec<Color>)>,
+    /// The images of the gallery whose colors are yet to be extracted.
+    gallery_pending: Vec<PathBuf>,
+    /// Counts the galleries opened and closed, so a batch finishing after its gallery was closed
+    /// isn't shown.
+    gallery_generation: u64,
+    /// The picker for the solid color wallpaper of a mode, while one is being picked.
+    color_picker: Option<(bool, ColorPickerModel)>,
+    /// What was typed into the settings' text inputs since they were last submitted.
+    text_drafts: HashMap<TextSetting, String>,
 }
 
+/// The number of config problems kept, the oldest ones are dropped first.
+const MAX_CONFIG_ERRORS: usize = 20;
+
+/// The solid color focus mode shows on all outputs.
+const FOCUS_WALLPAPER: [f32; 3] = [0.1, 0.1, 0.11];
+/// How much saturation focus mode takes away from the accent.
+const FOCUS_DESATURATION: f32 = 0.6;
+
+/// The number of gallery images extracted per update, so the popup stays responsive.
+const GALLERY_BATCH: usize = 4;
+
+/// The number of writes an animated accent change takes.
+const ACCENT_STEPS: usize = 8;
+/// The time between the writes of an animated accent change.
+const ACCENT_STEP: Duration = Duration::from_millis(30);
+
+/// How long after launching the wallpaper settings changes to the wallpapers are taken as the
+/// user's own rather than reverted.
+const ENFORCE_GRACE: Duration = Duration::from_secs(300);
+
+/// How often the clock is checked against the mode schedule.
+const SCHEDULE_CHECK: Duration = Duration::from_secs(30);
+
+/// The command launched by the settings buttons unless a mode has one of its own.
+const SETTINGS_COMMAND: &str = "cosmic-settings wallpaper";
+
+static SCROLLABLE_ID: LazyLock<scrollable::Id> = LazyLock::new(scrollable::Id::unique);
+
 impl AppModel {
-    fn update_bg(&mut self, is_dark: bool, context: &Context) {
-        if self.config.enabled {
-            let entries = if is_dark {
-                &self.config.dark
-            } else {
-                &self.config.light
-            };
-            let mut config = cosmic_bg_config::Config::load(context).unwrap();
-            entries
-                .iter()
-                .for_each(|e| config.set_entry(context, e.clone()).unwrap());
+    /// Applies the wallpapers stored for the mode along with the accents remembered for them,
+    /// returning how many entries were applied.
+    fn update_bg(&mut self, is_dark: bool, context: &Context) -> usize {
+        let entries = self.apply_wallpapers(is_dark, context);
+        for (output, color) in self.remembered_accents(&entries) {
+            tracing::debug!(
+                ?color,
+                ?output,
+                "applying the accent remembered for the wallpaper"
+            );
+            // Counts as picked by hand, so automatic selection leaves it be.
+            self.accent = Some(color);
+            if let Err(why) = self.apply_accent(color, is_dark, output.as_deref()) {
+                tracing::error!(%why, "failed to apply the remembered accent");
+            }
         }
+        entries.len()
     }
 
-    fn update_colors(&mut self, context: &Context) {
-        let config = cosmic_bg_config::Config::load(context).unwrap();
-        let backgrounds = if config.same_on_all {
-            vec![config.default_background]
-        } else {
-            config.backgrounds
-        };
+    /// Applies the wallpapers stored for the mode and nothing else, returning the entries that
+    /// were applied.
+    fn apply_wallpapers(&self, is_dark: bool, context: &Context) -> Vec<Entry> {
+        if !self.config.is_managing(is_dark) {
+            return vec![];
+        }
+        let entries = self.mode_entries(is_dark);
+        if !entries.is_empty() {
+            self.config.backend.apply(context, &entries);
+        }
+        entries
+    }
 
-        self.colors = backgrounds
+    /// The accents last picked for the image wallpapers of the entries, along with the output
+    /// each goes to, if remembering them is enabled. They're looked up the way
+    /// `active_wallpaper` stores them: by the wallpaper of the output, none being the default.
+    fn remembered_accents(&self, entries: &[Entry]) -> Vec<(Option<String>, Color)> {
+        if !self.config.remember_accent {
+            return vec![];
+        }
+        entries
             .iter()
-            .flat_map(|e| match e.source.clone() {
-                Source::Path(path_buf) => dominant_colors(path_buf),
-                Source::Color(color) => match color {
-                    cosmic_bg_config::Color::Single(color) => {
-                        let color = Srgb::from(color);
-                        vec![
-                            color.lighten(0.66).into(),
-                            color.lighten(0.33).into(),
-                            color.darken(0.33).into(),
-                            color.darken(0.66).into(),
-                        ]
-                    }
-                    cosmic_bg_config::Color::Gradient(gradient) => {
-                        let mut colors = gradient
-                            .colors
-                            .iter()
-                            .map(|&color| color.into())
-                            .collect::<Vec<_>>();
-                        gradient
-                            .colors
-                            .windows(2)
-                            .flat_map(|colors| {
-                                colors
-                                    .iter()
-                                    .map(|&color| Srgb::from(color))
-                                    .reduce(|l, r| l.mix(r, 0.5))
-                            })
-                            .for_each(|color| colors.push(color.into()));
-                        colors
-                    }
-                },
+            .filter_map(|e| {
+                let Source::Path(path) = &e.source else {
+                    return None;
+                };
+                let &color = self.config.wallpaper_accents.get(path)?;
+                let output = (e.output != "all").then(|| e.output.clone());
+                Some((output, color.into()))
             })
-            .collect_unique();
+            .collect()
     }
-}
 
-#[derive(Debug, Clone)]
-pub enum Message {
-    TogglePopup,
-    PopupClosed(Id),
-    ConfigUpdate(Config),
-    BgUpdate(Bg),
-    Toggle(bool),
-    OpenSettings(bool),
-    ChangeAccentColor(Color),
-    Token(TokenUpdate),
-}
+    /// The image shown on the output, or the default background's without one. Accents are
+    /// remembered by it.
+    fn active_wallpaper(&self, output: Option<&str>) -> Option<&PathBuf> {
+        let entry = output
+            .and_then(|output| self.bg.entries.iter().find(|e| e.output == output))
+            .or(self.bg.entries.first())?;
+        match &entry.source {
+            Source::Path(path) => Some(path),
+            _ => None,
+        }
+    }
 
-impl cosmic::Application for AppModel {
-    type Executor = cosmic::executor::Default;
-    type Flags = ();
-    type Message = Message;
-    const APP_ID: &'static str = "com.github.pstroka.BackgroundManager";
+    /// Switches to the mode, applies its wallpapers and, if given, the accent for that mode.
+    pub fn apply_mode(
+        &mut self,
+        is_dark: bool,
+        accent: Option<Color>,
+    ) -> Result<Applied, cosmic_config::Error> {
+        let mode_changed = self.core.system_theme_mode().is_dark != is_dark;
+        if mode_changed {
+            self.core
+                .system_theme_mode()
+                .set_is_dark(&ThemeMode::config()?, is_dark)?;
+        }
+        let wallpapers = self.update_bg(is_dark, &context()?);
+        if let Some(color) = accent {
+            self.accent = Some(color);
+            self.apply_accent(color, is_dark, None)?;
+        }
+        Ok(Applied {
+            mode_changed,
+            wallpapers,
+            accent,
+        })
+    }
 
-    fn core(&self) -> &cosmic::Core {
-        &self.core
+    /// Whether the shown backgrounds are missing some of the mode's wallpapers and should get
+    /// them back. Changes made while the wallpaper settings launched from the applet may still
+    /// be open are the user's and never reverted.
+    fn should_enforce(&self, is_dark: bool, shown: &[Entry]) -> bool {
+        if self.paused
+            || self.focus.is_some()
+            || !self.config.enforce_wallpapers
+            || !self.config.enabled(is_dark)
+        {
+            return false;
+        }
+        if self
+            .settings_launched
+            .is_some_and(|launched| launched.elapsed() < ENFORCE_GRACE)
+        {
+            return false;
+        }
+        let expected = self.mode_entries(is_dark);
+        !expected.iter().all(|e| shown.contains(e))
     }
 
-    fn core_mut(&mut self) -> &mut cosmic::Core {
-        &mut self.core
+    /// The entries of the mode to apply, leaving out the ones stored for outputs that aren't
+    /// connected, so each connected output only gets its own.
+    fn mode_entries(&self, is_dark: bool) -> Vec<Entry> {
+        let mut entries = self
+            .config
+            .wallpaper_scope
+            .entries(self.config.entries(is_dark), self.connected.values());
+        // Until the outputs are known, every entry is applied.
+        if !self.connected.is_empty() {
+            entries
+                .retain(|e| e.output == "all" || self.connected.values().any(|o| *o == e.output));
+        }
+        entries
     }
 
-    fn init(
-        core: cosmic::Core,
-        _flags: Self::Flags,
-    ) -> (Self, Task<cosmic::Action<Self::Message>>) {
-        let app = AppModel {
-            core,
-            config_handler: Config::config().ok(),
-            config: Config::config()
-                .map(|context| match Config::get_entry(&context) {
-                    Ok(config) => config,
-                    Err((_errors, config)) => {
-                        // for why in errors {
-                        //     tracing::error!(%why, "error loading app config");
-                        // }
+    /// Saves a change to the app config, logging the failure and flagging it in the popup
+    /// instead of panicking if the config can't be written. Returns whether anything changed, or
+    /// `None` if saving failed.
+    fn save(
+        &mut self,
+        write: impl FnOnce(&mut Config, &cosmic_config::Config) -> Result<bool, cosmic_config::Error>,
+    ) -> Option<bool> {
+        let Some(handler) = self.config_handler.as_ref() else {
+            tracing::error!("no app config to save to");
+            self.save_failed = true;
+            return None;
+        };
+        match write(&mut self.config, handler) {
+            Ok(changed) => {
+                self.save_failed = false;
+                Some(changed)
+            }
+            Err(why) => {
+                tracing::error!(%why, "failed to save app config");
+                self.save_failed = true;
+                None
+            }
+        }
+    }
 
-                        config
+    fn capture_output_names(&mut self, entries: &[Entry]) {
+        let mut names = self.config.output_names.clone();
+        for entry in entries {
+            if let Some(name) = self.outputs.get(&entry.output) {
+                names.insert(entry.output.clone(), name.clone());
+            }
+        }
+        if names != self.config.output_names {
+            self.save(|config, handler| config.set_output_names(handler, names));
+        }
+    }
+
+    fn mode_item<'a>(
+        &'a self,
+        title: String,
+        entries: &[Entry],
+        is_dark: bool,
+    ) -> Element<'a, Message> {
+        let outputs = entries
+            .iter()
+            .filter(|e| e.output != "all")
+            .map(|e| self.config.output_label(&e.output))
+            .collect::<Vec<_>>();
+        let button = if self.config.enabled(is_dark) && !self.config.is_managing(is_dark) {
+            menu_button(column![text(title), text::caption(fl!("no-wallpapers"))])
+        } else if outputs.is_empty() {
+            menu_button(text(title))
+        } else {
+            menu_button(column![text(title), text::caption(outputs.join(", "))])
+        }
+        .on_press(Message::OpenModeSettings(is_dark));
+        let solid_color: Element<'a, Message> = match &self.color_picker {
+            Some((picking, model)) if *picking == is_dark => {
+                padded_control(model.builder(Message::ColorPicker).build(
+                    fl!("recent-colors"),
+                    fl!("copy-to-clipboard"),
+                    fl!("copied-to-clipboard"),
+                ))
+                .into()
+            }
+            _ => menu_button(text(fl!("solid-color")))
+                .on_press(Message::PickModeColor(is_dark))
+                .into(),
+        };
+        let Some(first) = entries.first() else {
+            return column![button, solid_color].into();
+        };
+        let selected = entries
+            .iter()
+            .all(|e| e.scaling_mode == first.scaling_mode)
+            .then_some(match first.scaling_mode {
+                ScalingMode::Zoom => 0,
+                ScalingMode::Fit(_) => 1,
+                ScalingMode::Stretch => 2,
+            });
+        let preview = (self.preview.is_none() && is_dark != self.core.system_theme_mode().is_dark)
+            .then(|| menu_button(text(fl!("preview"))).on_press(Message::PreviewMode(is_dark)));
+        let expanded = self.entries_expanded == Some(is_dark);
+        let toggle = menu_button(text(if expanded {
+            fl!("hide-wallpapers")
+        } else {
+            fl!("show-wallpapers")
+        }))
+        .on_press(Message::ToggleEntries(is_dark));
+        column![
+            button,
+            solid_color,
+            padded_item(
+                fl!("fit"),
+                dropdown(&self.scaling_modes, selected, move |i| {
+                    Message::SetScalingMode(is_dark, i)
+                })
+            )
+        ]
+        .push_maybe(preview)
+        .push(toggle)
+        .push_maybe(expanded.then(|| self.entries_section(entries, is_dark)))
+        .into()
+    }
+
+    /// The wallpapers of the mode, each with a preview and a button removing it.
+    fn entries_section<'a>(&'a self, entries: &[Entry], is_dark: bool) -> Element<'a, Message> {
+        entries
+            .iter()
+            .enumerate()
+            .fold(column![], |section, (i, entry)| {
+                let preview: Element<'a, Message> = match &entry.source {
+                    Source::Path(path) => match self.thumbnails.get(path) {
+                        Some(handle) => image(handle.clone())
+                            .width(Length::Fixed(ENTRY_THUMBNAIL))
+                            .into(),
+                        None => text::caption(source_label(&entry.source)).into(),
+                    },
+                    Source::Color(cosmic_bg_config::Color::Single([r, g, b])) => color_button(
+                        None,
+                        Some(Color::from_rgb(*r, *g, *b)),
+                        Length::Fixed(ENTRY_THUMBNAIL),
+                    )
+                    .into(),
+                    Source::Color(cosmic_bg_config::Color::Gradient(gradient)) => {
+                        let colors = gradient
+                            .colors
+                            .iter()
+                            .map(|&color| Srgb::from(color).into())
+                            .collect::<Vec<_>>();
+                        container(palette_strip(&colors))
+                            .width(Length::Fixed(ENTRY_THUMBNAIL))
+                            .into()
                     }
+                };
+                let output = if entry.output == "all" {
+                    fl!("all-outputs")
+                } else {
+                    self.config.output_label(&entry.output)
+                };
+                section.push(padded_control(
+                    row![
+                        preview,
+                        text(output).width(Length::Fill),
+                        button::text(fl!("remove")).on_press(Message::RemoveEntry(is_dark, i)),
+                    ]
+                    .spacing(8)
+                    .align_y(Alignment::Center),
+                ))
+            })
+            .into()
+    }
+
+    /// Loads the thumbnails of the image wallpapers of the mode that aren't loaded yet, decoding
+    /// them on a blocking thread. The wallpapers are listed by name until theirs arrive.
+    fn load_thumbnails(&self, is_dark: bool) -> Task<cosmic::Action<Message>> {
+        let paths = self
+            .config
+            .entries(is_dark)
+            .iter()
+            .filter_map(|e| match &e.source {
+                Source::Path(path) if !self.thumbnails.contains_key(path) => Some(path.clone()),
+                _ => None,
+            })
+            .collect_unique::<Vec<_>>();
+        if paths.is_empty() {
+            return Task::none();
+        }
+        let load = move || {
+            paths
+                .into_iter()
+                .filter_map(|path| {
+                    let Some((_, thumbnail, _)) = load_image_with_thumbnail(path.clone()) else {
+                        tracing::warn!(?path, "failed to load wallpaper thumbnail");
+                        return None;
+                    };
+                    let (width, height) = thumbnail.dimensions();
+                    let handle = image::Handle::from_rgba(width, height, thumbnail.into_raw());
+                    Some((path, handle))
                 })
-                .unwrap_or_default(),
-            ..Default::default()
+                .collect::<Vec<_>>()
         };
+        Task::perform(
+            async move {
+                tokio::task::spawn_blocking(load)
+                    .await
+                    .unwrap_or_else(|why| {
+                        tracing::error!(%why, "loading wallpaper thumbnails failed");
+                        vec![]
+                    })
+            },
+            |thumbnails| cosmic::Action::App(Message::ThumbnailsLoaded(thumbnails)),
+        )
+    }
 
-        (app, Task::none())
+    /// Replaces the wallpapers of the mode with a solid color on all outputs.
+    fn set_mode_color(&mut self, is_dark: bool, color: Color) {
+        self.set_mode_source(
+            is_dark,
+            Source::Color(cosmic_bg_config::Color::Single([color.r, color.g, color.b])),
+        );
     }
 
-    fn on_close_requested(&self, id: Id) -> Option<Message> {
-        Some(Message::PopupClosed(id))
+    /// Makes the source the wallpaper of all outputs in the mode.
+    fn set_mode_source(&mut self, is_dark: bool, source: Source) {
+        let entries = self.entries_everywhere(is_dark, source);
+        if self
+            .save(|config, handler| config.set_entries(handler, is_dark, entries))
+            .is_none()
+        {
+            return;
+        }
+        if is_dark == self.core.system_theme_mode().is_dark {
+            if let Some(context) = bg_context() {
+                self.update_bg(is_dark, &context);
+            }
+        }
     }
 
-    fn view(&self) -> Element<'_, Self::Message> {
-        self.core
-            .applet
-            .icon_button("com.github.pstroka.BackgroundManager-symbolic")
-            .on_press_down(Message::TogglePopup)
-            .into()
+    /// An `"all"` entry showing the source, and one for every connected output or output with a
+    /// wallpaper of its own in the mode, or those would keep showing theirs.
+    fn entries_everywhere(&self, is_dark: bool, source: Source) -> Vec<Entry> {
+        let mut outputs = self.connected.values().cloned().collect::<BTreeSet<_>>();
+        outputs.extend(
+            self.bg
+                .entries
+                .iter()
+                .chain(self.config.entries(is_dark))
+                .map(|e| e.output.clone()),
+        );
+        outputs.remove("all");
+        std::iter::once("all".to_string())
+            .chain(outputs)
+            .map(|output| Entry::new(output, source.clone()))
+            .collect()
     }
 
-    fn view_window(&self, _id: Id) -> Element<'_, Self::Message> {
-        let content_list = column![
-            padded_item(
-                fl!("switcher-text"),
-                toggler(self.config.enabled).on_toggle(Message::Toggle)
-            ),
-            padded_control(divider::horizontal::default()),
-            menu_button(text(fl!("settings-dark"))).on_press(Message::OpenSettings(true)),
-            padded_control(divider::horizontal::default()),
-            menu_button(text(fl!("settings-light"))).on_press(Message::OpenSettings(false)),
-            padded_control(divider::horizontal::default()),
-            padded_item(
-                fl!("accent-color"),
-                row(self.colors.iter().map(|color| {
-                    color_button(
-                        Some(Message::ChangeAccentColor(*color)),
-                        Some(*color),
-                        Length::Fill,
-                    )
-                    .into()
-                }))
-                .spacing(8)
-                .wrap()
-            ),
-        ]
-        .padding([8, 0, 8, 0]);
+    /// Applies the accent to the theme of the mode, or of the output if one is given.
+    fn apply_accent(
+        &mut self,
+        color: Color,
+        is_dark: bool,
+        output: Option<&str>,
+    ) -> Result<(), cosmic_config::Error> {
+        if !self.config.manage_theme {
+            return Ok(());
+        }
+        let color = self.write_theme(color, is_dark, output, ThemeField::Accent)?;
+        if is_dark == self.core.system_theme_mode().is_dark {
+            self.theme_accent = Some(color);
+        }
+        if self.config.gtk_accent {
+            gtk::set_accent(color);
+        }
+        Ok(())
+    }
 
-        self.core.applet.popup_container(content_list).into()
+    /// Applies the window hint alone to the theme of the mode, leaving the accent as it is.
+    fn apply_window_hint(
+        &mut self,
+        color: Color,
+        is_dark: bool,
+    ) -> Result<(), cosmic_config::Error> {
+        // A turned off window hint stays off, as accent writes keep it.
+        if !self.config.manage_theme || !self.config.window_hint_enabled {
+            return Ok(());
+        }
+        self.write_theme(color, is_dark, None, ThemeField::WindowHint)
+            .map(|_| ())
     }
 
-    fn subscription(&self) -> Subscription<Self::Message> {
-        Subscription::batch(vec![
-            activation_token_subscription(0).map(Message::Token),
-            self.core()
-                .watch_config::<Config>(Self::APP_ID)
-                .map(|update| {
-                    // for why in update.errors {
-                    //     tracing::error!(?why, "app config error");
-                    // }
+    /// Writes the color to the field of the theme of the mode or output, returning the color as
+    /// written.
+    fn write_theme(
+        &mut self,
+        color: Color,
+        is_dark: bool,
+        output: Option<&str>,
+        field: ThemeField,
+    ) -> Result<Color, cosmic_config::Error> {
+        let mode = usize::from(is_dark);
+        let configs = match self.theme_configs[mode].take() {
+            Some(configs) => configs,
+            None => theme_configs(is_dark, output)?,
+        };
+        if self.original_theme[mode].is_none() {
+            let builder = ThemeBuilder::get_entry(&configs.0).unwrap_or_else(|(_, b)| b);
+            self.original_theme[mode] = Some((builder.accent, builder.window_hint));
+        }
+        let result = self.write_theme_to(&configs, color, is_dark, field);
+        // The configs are reopened on the next write if this one failed.
+        if result.is_ok() {
+            self.theme_configs[mode] = Some(configs);
+        }
+        result
+    }
 
-                    Message::ConfigUpdate(update.config)
-                }),
-            self.core()
-                .watch_config::<Bg>(cosmic_bg_config::NAME)
-                .map(|update| Message::BgUpdate(update.config)),
-        ])
+    /// Writes back the accent and window hint the theme of the mode had before the applet first
+    /// changed them, to the builder and the theme built from it.
+    fn revert_theme(&mut self, is_dark: bool) -> Result<(), cosmic_config::Error> {
+        let mode = usize::from(is_dark);
+        let Some((accent, window_hint)) = self.original_theme[mode] else {
+            return Ok(());
+        };
+        let configs = match self.theme_configs[mode].take() {
+            Some(configs) => configs,
+            None => theme_configs(is_dark, None)?,
+        };
+        let (builder_config, theme_config) = &configs;
+        let previous = ThemeBuilder::get_entry(builder_config).unwrap_or_else(|(_, b)| b);
+        let mut builder = previous.clone();
+        builder.accent = accent;
+        builder.window_hint = window_hint;
+        let theme = write_builder_to(
+            &(builder_config, theme_config),
+            is_dark,
+            "accent, window_hint",
+            (accent, window_hint),
+            &previous,
+            &builder,
+        )?;
+        self.theme_configs[mode] = Some(configs);
+        if self.config.gtk_accent {
+            gtk::set_accent(theme.accent.base.into());
+        }
+        // Only a single change is undone, the next write captures the theme again.
+        self.original_theme[mode] = None;
+        if is_dark == self.core.system_theme_mode().is_dark {
+            self.theme_accent = current_accent(is_dark);
+        }
+        Ok(())
     }
 
-    fn update(&mut self, message: Self::Message) -> Task<cosmic::Action<Self::Message>> {
-        match message {
-            Message::ConfigUpdate(config) => {
-                self.config = config;
-            }
-            Message::Toggle(toggled) => {
-                self.config
-                    .set_enabled(self.config_handler.as_ref().unwrap(), toggled)
-                    .unwrap();
-            }
-            Message::TogglePopup => {
-                return if let Some(p) = self.popup.take() {
-                    destroy_popup(p)
+    /// Writes the color to the field of the theme builder, then the theme built from it. If the
+    /// theme can't be written, both are rolled back to what they were, so they never disagree.
+    fn write_theme_to(
+        &self,
+        (builder_config, theme_config): &(cosmic_config::Config, cosmic_config::Config),
+        color: Color,
+        is_dark: bool,
+        field: ThemeField,
+    ) -> Result<Color, cosmic_config::Error> {
+        let builder =
+            ThemeBuilder::get_entry(builder_config).unwrap_or_else(|(errors, builder)| {
+                for why in errors {
+                    tracing::warn!(%why, "error loading theme builder");
+                }
+                builder
+            });
+        let previous = builder.clone();
+        let (color, builder, key) = match field {
+            ThemeField::Accent => {
+                let color = if self.config.high_contrast_accent {
+                    let background = builder.clone().build().background.base;
+                    colors::ensure_contrast(color, background.into(), colors::MIN_CONTRAST)
                 } else {
-                    let new_id = Id::unique();
-                    self.popup.replace(new_id);
-                    let popup_settings = self.core.applet.get_popup_settings(
-                        self.core.main_window_id().unwrap(),
-                        new_id,
-                        None,
-                        None,
-                        None,
-                    );
-                    get_popup(popup_settings)
+                    color
+                };
+                let mut builder = builder.accent(color.into());
+                if !self.config.window_hint_enabled {
+                    builder.window_hint = None;
+                } else if self.window_hint().is_none() {
+                    builder.window_hint = Some(color.into());
                 }
+                // A window hint picked separately is left as it is.
+                (color, builder, "accent")
             }
-            Message::PopupClosed(id) => {
-                if self.popup.as_ref() == Some(&id) {
-                    self.popup = None;
-                }
+            ThemeField::WindowHint => {
+                let mut builder = builder;
+                builder.window_hint = Some(color.into());
+                (color, builder, "window_hint")
             }
-            Message::BgUpdate(config) => {
-                if config.entries.is_empty() {
-                    return Task::none();
-                }
-                self.update_colors(&context().unwrap());
-                let is_dark = self.core.system_theme_mode().is_dark;
-                if is_dark && config.entries != self.config.dark {
-                    self.config
-                        .set_dark(self.config_handler.as_ref().unwrap(), config.entries)
-                        .unwrap();
-                } else if !is_dark && config.entries != self.config.light {
-                    self.config
-                        .set_light(self.config_handler.as_ref().unwrap(), config.entries)
-                        .unwrap();
-                }
-            }
-            Message::OpenSettings(is_dark) => {
-                self.core
-                    .system_theme_mode()
-                    .set_is_dark(&ThemeMode::config().unwrap(), is_dark)
-                    .unwrap();
-                if let Some(tx) = self.token_tx.as_ref() {
-                    let _ = tx.send(TokenRequest {
-                        app_id: Self::APP_ID.to_string(),
-                        exec: "cosmic-settings wallpaper".to_string(),
-                    });
-                }
+        };
+        write_builder_to(
+            &(builder_config, theme_config),
+            is_dark,
+            key,
+            color,
+            &previous,
+            &builder,
+        )?;
+        Ok(color)
+    }
+
+    /// Applies the focus wallpaper and a muted accent, keeping what they replace to restore.
+    fn enter_focus(&mut self, context: &Context) {
+        let is_dark = self.core.system_theme_mode().is_dark;
+        let accent = self.theme_accent;
+        let entries = self.entries_everywhere(
+            is_dark,
+            Source::Color(cosmic_bg_config::Color::Single(FOCUS_WALLPAPER)),
+        );
+        // The outputs showing the shared wallpaper get an entry of their own while focused, so
+        // they're given the shared one back when focus mode is left.
+        let mut restored = self.bg.entries.clone();
+        if let Some(shared) = restored.iter().find(|e| e.output == "all").cloned() {
+            let following = entries
+                .iter()
+                .filter(|focused| restored.iter().all(|e| e.output != focused.output))
+                .map(|focused| Entry {
+                    output: focused.output.clone(),
+                    ..shared.clone()
+                })
+                .collect::<Vec<_>>();
+            restored.extend(following);
+        }
+        self.focus = Some(Snapshot::new(is_dark, restored, accent.map(Into::into)));
+        self.config.backend.apply(context, &entries);
+        if let Some(accent) = accent {
+            let muted = colors::adjust(accent, -FOCUS_DESATURATION, 0.0);
+            if let Err(why) = self.apply_accent(muted, is_dark, None) {
+                tracing::error!(%why, "failed to apply focus accent");
             }
-            Message::Token(u) => match u {
-                TokenUpdate::Init(tx) => {
-                    self.token_tx = Some(tx);
-                }
-                TokenUpdate::Finished => {
-                    self.token_tx = None;
-                }
-                TokenUpdate::ActivationToken { token, exec } => {
-                    let mut exec = exec.split_whitespace();
-                    if let Some(cmd) = exec.next() {
-                        let mut cmd = std::process::Command::new(cmd);
-                        cmd.args(exec);
-                        if let Some(token) = token {
-                            cmd.env("XDG_ACTIVATION_TOKEN", &token);
-                            cmd.env("DESKTOP_STARTUP_ID", &token);
-                        }
-                        tokio::spawn(cosmic::process::spawn(cmd));
-                    }
-                }
-            },
-            Message::ChangeAccentColor(color) => {
-                let (builder_config, theme_config) = if self.core.system_theme_mode().is_dark {
-                    (
-                        ThemeBuilder::dark_config().unwrap(),
-                        Theme::dark_config().unwrap(),
-                    )
-                } else {
-                    (
-                        ThemeBuilder::light_config().unwrap(),
-                        Theme::light_config().unwrap(),
-                    )
-                };
-                let mut builder = ThemeBuilder::get_entry(&builder_config)
-                    .unwrap()
-                    .accent(color.into());
-                builder.window_hint = Some(color.into());
-                builder.write_entry(&builder_config).unwrap();
-                let theme = builder.build();
-                theme.write_entry(&theme_config).unwrap();
+        }
+    }
+
+    /// Puts back the backgrounds and accent focus mode replaced.
+    fn leave_focus(&mut self, context: &Context) {
+        let Some(snapshot) = self.focus.take() else {
+            return;
+        };
+        self.config.backend.apply(context, &snapshot.entries);
+        if let Some(accent) = snapshot.accent {
+            if let Err(why) = self.apply_accent(accent.into(), snapshot.is_dark, None) {
+                tracing::error!(%why, "failed to restore accent after focus mode");
             }
         }
-        Task::none()
     }
 
-    fn style(&self) -> Option<cosmic::iced_runtime::Appearance> {
-        Some(cosmic::applet::style())
+    /// Whether picked accents go to the dark mode theme.
+    fn accent_target(&self) -> bool {
+        self.accent_target
+            .unwrap_or(self.core.system_theme_mode().is_dark)
     }
 
-    fn system_theme_mode_update(
+    fn change_accent(
         &mut self,
-        _keys: &[&'static str],
-        new_theme: &cosmic::cosmic_theme::ThemeMode,
-    ) -> Task<cosmic::Action<Self::Message>> {
-        let context = context().unwrap();
-        self.update_bg(new_theme.is_dark, &context);
-        self.update_colors(&context);
-        Task::none()
+        color: Color,
+        is_dark: bool,
+        output: Option<String>,
+    ) -> Task<cosmic::Action<Message>> {
+        let current = is_dark == self.core.system_theme_mode().is_dark;
+        // The accent of the other mode isn't shown, so it isn't tracked either.
+        if current {
+            self.accent = Some(color);
+            self.adjustment = (0.0, 0.0);
+        }
+        let from = self
+            .theme_accent
+            .filter(|_| current && self.config.animate_accent && self.config.manage_theme);
+        let Some(from) = from else {
+            if let Err(why) = self.apply_accent(color, is_dark, output.as_deref()) {
+                tracing::error!(%why, "failed to apply accent color");
+            }
+            return Task::none();
+        };
+        self.accent_animation += 1;
+        self.animation_writes.clear();
+        let animation = self.accent_animation;
+        // The last step applies the accent itself, so it ends up exact.
+        let steps = (1..ACCENT_STEPS)
+            .map(|i| {
+                (
+                    colors::blend(color, from, 1.0 - i as f32 / ACCENT_STEPS as f32),
+                    false,
+                )
+            })
+            .chain([(color, true)]);
+        Task::batch(steps.enumerate().map(|(i, (color, last))| {
+            let delay = ACCENT_STEP * (i as u32 + 1);
+            let output = output.clone();
+            Task::perform(tokio::time::sleep(delay), move |_| {
+                cosmic::Action::App(Message::AccentStep {
+                    animation,
+                    is_dark,
+                    output,
+                    color,
+                    last,
+                })
+            })
+        }))
     }
-}
 
-fn padded_item<'a, Message: 'static>(
-    title: impl Into<Cow<'a, str>> + 'a,
-    widget: impl Into<Element<'a, Message>> + 'a,
-) -> cosmic::widget::Container<'a, Message, cosmic::Theme> {
-    padded_control(item_row(vec![
-        text(title)
-            .wrapping(Wrapping::Word)
+    fn accent_section(&self) -> Element<'_, Message> {
+        let limit = usize::from(self.config.color_count).min(MAX_SWATCHES);
+        let shown = if self.swatches_expanded {
+            self.colors.len()
+        } else {
+            self.colors.len().min(limit)
+        };
+        let overflow = (self.colors.len() > limit).then(|| {
+            let label = if self.swatches_expanded {
+                fl!("fewer-swatches")
+            } else {
+                fl!("more-swatches", count = self.colors.len() - shown)
+            };
+            menu_button(text(label)).on_press(Message::ToggleSwatches)
+        });
+        let swatches = self.config.extract_colors.then(|| {
+            column![padded_item(
+                fl!("accent-color"),
+                row(self.colors[..shown].iter().map(|&color| swatch(
+                    // Only the preview is transformed, picking still applies the real color.
+                    self.vision
+                        .map_or(color, |vision| colors::simulate(color, vision)),
+                    self.accent == Some(color),
+                    Message::PickSwatch(color)
+                )))
+                .spacing(8)
+                .wrap()
+            ),]
+            .push_maybe(overflow)
+            .push(padded_item(
+                fl!("color-count", count = self.config.color_count),
+                slider(
+                    1..=MAX_SWATCHES as u8,
+                    self.config.color_count,
+                    Message::SetColorCount,
+                ),
+            ))
+            .push(padded_item(
+                fl!("accent-target"),
+                dropdown(
+                    &self.modes,
+                    Some(if self.accent_target() { 0 } else { 1 }),
+                    |i| Message::SetAccentTarget(i == 0),
+                ),
+            ))
+            .push(padded_control(palette_strip(&self.colors)))
+            .push(padded_item(
+                fl!("vision"),
+                dropdown(
+                    &self.visions,
+                    Some(match self.vision {
+                        None => 0,
+                        Some(colors::ColorVision::Protanopia) => 1,
+                        Some(colors::ColorVision::Deuteranopia) => 2,
+                        Some(colors::ColorVision::Tritanopia) => 3,
+                    }),
+                    Message::SetVision,
+                ),
+            ))
+        });
+        let current = self.theme_accent.map(|color| {
+            padded_item(
+                fl!("current-accent"),
+                labeled_swatch(
+                    color,
+                    colors::to_hex(color),
+                    Message::ChangeAccentColor(color, None),
+                ),
+            )
+        });
+        column![]
+            .push_maybe(current)
+            .push_maybe(swatches)
+            .push_maybe(self.adjustment_section())
+            .push_maybe(self.accent.map(|_| {
+                padded_item(
+                    fl!("blend"),
+                    slider(0.0..=1.0, self.blend, Message::SetBlend).step(0.05),
+                )
+            }))
+            .push_maybe(self.config.extract_colors.then(|| {
+                column![
+                    menu_button(text(fl!("random-accent"))).on_press(Message::RandomAccent),
+                    menu_button(text(fl!("copy-palette"))).on_press(Message::CopyPalette),
+                ]
+            }))
+            .push(
+                menu_button(text(fl!("revert-accent"))).on_press_maybe(
+                    self.original_theme[usize::from(self.accent_target())]
+                        .is_some()
+                        .then_some(Message::RevertAccent),
+                ),
+            )
+            .push(menu_button(text(fl!("import-palette"))).on_press(Message::ImportPalette))
+            .push_maybe(self.imported_section())
+            .push(menu_button(text(fl!("open-gallery"))).on_press(Message::OpenGallery))
+            .push_maybe(self.gallery_section())
+            .push(padded_item(
+                fl!("window-hint"),
+                toggler(self.config.window_hint_enabled).on_toggle(Message::ToggleWindowHint),
+            ))
+            .push_maybe(
+                (self.config.window_hint_enabled && self.config.separate_window_hint)
+                    .then(|| self.secondary_section()),
+            )
+            .push(padded_control(divider::horizontal::default()))
+            .push(self.history_section())
+            .into()
+    }
+
+    /// The color picked for the window hint, if it doesn't follow the accent.
+    fn window_hint(&self) -> Option<Color> {
+        self.secondary.filter(|_| self.config.separate_window_hint)
+    }
+
+    fn secondary_section(&self) -> Element<'_, Message> {
+        let follow = self.secondary.map(|_| {
+            menu_button(text(fl!("window-hint-follows-accent")))
+                .on_press(Message::ChangeWindowHint(None))
+        });
+        column![padded_item(
+            fl!("swatches-set-window-hint"),
+            toggler(self.config.pick_window_hint).on_toggle(Message::SetPickWindowHint),
+        )]
+        .push_maybe(follow)
+        .into()
+    }
+
+    fn imported_section(&self) -> Option<Element<'_, Message>> {
+        if self.imported.is_empty() {
+            return None;
+        }
+        let suggested = colors::select_accent(&self.imported, self.config.accent_temperature_bias);
+        Some(
+            column![padded_item(
+                fl!("imported-palette"),
+                row(self.imported.iter().map(|&color| swatch(
+                    color,
+                    self.accent == Some(color),
+                    Message::ChangeAccentColor(color, None)
+                )))
+                .spacing(8)
+                .wrap()
+            ),]
+            .push_maybe(suggested.map(|color| {
+                menu_button(text(fl!("apply-suggested-accent")))
+                    .on_press(Message::ChangeAccentColor(color, None))
+            }))
+            .push(menu_button(text(fl!("dismiss"))).on_press(Message::DismissImported))
+            .into(),
+        )
+    }
+
+    fn adjust_accent(&mut self, saturation: f32, lightness: f32) {
+        let Some(accent) = self.accent else {
+            return;
+        };
+        self.adjustment = (saturation, lightness);
+        let color = colors::adjust(accent, saturation, lightness);
+        if let Err(why) = self.apply_accent(color, self.core.system_theme_mode().is_dark, None) {
+            tracing::error!(%why, "failed to apply accent color");
+        }
+    }
+
+    fn adjustment_section(&self) -> Option<Element<'_, Message>> {
+        self.accent?;
+        let (saturation, lightness) = self.adjustment;
+        Some(
+            column![
+                padded_item(
+                    fl!("saturation"),
+                    slider(-1.0..=1.0, saturation, Message::AdjustSaturation).step(0.05)
+                ),
+                padded_item(
+                    fl!("lightness"),
+                    slider(-1.0..=1.0, lightness, Message::AdjustLightness).step(0.05)
+                ),
+            ]
+            .into(),
+        )
+    }
+
+    fn errors_section(&self) -> Option<Element<'_, Message>> {
+        if self.config_errors.is_empty() {
+            return None;
+        }
+        let toggle = menu_button(text(fl!("config-errors", count = self.config_errors.len())))
+            .on_press(Message::ToggleErrors);
+        if !self.errors_expanded {
+            return Some(column![toggle, padded_control(divider::horizontal::default())].into());
+        }
+        let section = self
+            .config_errors
+            .iter()
+            .fold(column![toggle], |section, why| {
+                section.push(padded_control(text::caption(why)))
+            })
+            .push(menu_button(text(fl!("dismiss"))).on_press(Message::DismissErrors))
+            .push(padded_control(divider::horizontal::default()));
+        Some(section.into())
+    }
+
+    /// Loads the thumbnails of the gallery images on a blocking thread, extracting the colors
+    /// of the ones that aren't cached from the same thumbnails.
+    fn load_gallery(
+        &self,
+        paths: Vec<PathBuf>,
+        extraction: colors::Extraction,
+    ) -> Task<cosmic::Action<Message>> {
+        let generation = self.gallery_generation;
+        let paths = paths
+            .into_iter()
+            .map(|path| {
+                let cached = self.cache.has(&path, extraction);
+                (path, cached)
+            })
+            .collect::<Vec<_>>();
+        let load = move || {
+            paths
+                .into_iter()
+                .filter_map(|(path, cached)| {
+                    let (thumbnail, colors) = gallery_image(path.clone(), extraction, cached)?;
+                    Some((path, thumbnail, colors))
+                })
+                .collect::<Vec<_>>()
+        };
+        Task::perform(
+            async move {
+                tokio::task::spawn_blocking(load)
+                    .await
+                    .unwrap_or_else(|why| {
+                        tracing::error!(%why, "loading gallery images failed");
+                        vec![]
+                    })
+            },
+            move |images| {
+                cosmic::Action::App(Message::GalleryExtracted(generation, extraction, images))
+            },
+        )
+    }
+
+    /// The cached colors of each image, none for the ones that failed to extract.
+    fn gallery_colors(
+        &mut self,
+        images: Vec<(PathBuf, image::Handle)>,
+        extraction: colors::Extraction,
+    ) -> Vec<(PathBuf, image::Handle, Vec<Color>)> {
+        images
+            .into_iter()
+            .map(|(path, thumbnail)| {
+                let colors = self.cache.get(&path, extraction).unwrap_or_default();
+                (path, thumbnail, colors)
+            })
+            .collect()
+    }
+
+    fn gallery_section(&self) -> Option<Element<'_, Message>> {
+        if self.gallery.is_empty() && self.gallery_pending.is_empty() {
+            return None;
+        }
+        let grid = row(self.gallery.iter().map(|(path, thumbnail, colors)| {
+            button::custom(
+                column![
+                    image(thumbnail.clone())
+                        .width(Length::Fixed(GALLERY_THUMBNAIL))
+                        .height(Length::Fixed(GALLERY_THUMBNAIL * 9.0 / 16.0)),
+                    container(palette_strip(colors)).width(Length::Fixed(GALLERY_THUMBNAIL)),
+                ]
+                .spacing(4),
+            )
+            .class(cosmic::theme::Button::Image)
+            .on_press(Message::PickGalleryImage(path.clone()))
+            .into()
+        }))
+        .spacing(8)
+        .wrap();
+        let status = (!self.gallery_pending.is_empty()).then(|| {
+            padded_control(text::caption(fl!(
+                "gallery-loading",
+                count = self.gallery_pending.len()
+            )))
+        });
+        Some(
+            column![padded_control(
+                cosmic::widget::scrollable(grid).height(Length::Fixed(300.0))
+            )]
+            .push_maybe(status)
+            .push(menu_button(text(fl!("close-gallery"))).on_press(Message::CloseGallery))
+            .into(),
+        )
+    }
+
+    fn outputs_section(&self) -> Element<'_, Message> {
+        let toggle = menu_button(text(fl!("outputs"))).on_press(Message::ToggleOutputs);
+        if !self.outputs_expanded {
+            return toggle.into();
+        }
+        let mut section = column![toggle];
+        if self.bg.same_on_all {
+            section = section.push(padded_control(text::caption(fl!("same-on-all"))));
+        }
+        let section = self.bg.entries.iter().fold(section, |section, entry| {
+            let output = if entry.output == "all" {
+                fl!("all-outputs")
+            } else {
+                self.config.output_label(&entry.output)
+            };
+            section.push(padded_item(output, text(source_label(&entry.source))))
+        });
+        let mut connected = self.connected.values().collect::<Vec<_>>();
+        connected.sort();
+        connected
+            .into_iter()
+            .fold(section, |section, output| {
+                let selected = self.config.accent_source_output.as_ref() == Some(output);
+                let output = output.clone();
+                section.push(padded_item(
+                    fl!("accent-source", output = self.config.output_label(&output)),
+                    toggler(selected)
+                        .on_toggle(move |v| Message::SetAccentSource(v.then(|| output.clone()))),
+                ))
+            })
+            .into()
+    }
+
+    fn history_section(&self) -> Element<'_, Message> {
+        let toggle = menu_button(text(fl!("history"))).on_press(Message::ToggleHistory);
+        if !self.history_expanded {
+            return toggle.into();
+        }
+        self.config
+            .history
+            .iter()
+            .enumerate()
+            .fold(column![toggle], |column, (i, record)| {
+                column.push(padded_item(
+                    record.label.clone(),
+                    row(record.colors.iter().map(|&color| {
+                        swatch(
+                            color.into(),
+                            record.chosen == Some(color),
+                            Message::ApplyHistoryAccent(i, color.into()),
+                        )
+                    }))
+                    .spacing(8)
+                    .wrap(),
+                ))
+            })
+            .into()
+    }
+
+    fn open_popup(&self, id: Id) -> Task<cosmic::Action<Message>> {
+        let mut popup_settings = self.core.applet.get_popup_settings(
+            self.core.main_window_id().unwrap(),
+            id,
+            None,
+            None,
+            None,
+        );
+        popup_settings.positioner.size_limits = Limits::NONE
+            .min_width(300.0)
+            .max_width(400.0)
+            .max_height(800.0);
+        get_popup(popup_settings)
+    }
+
+    fn open_settings(&mut self, exec: String) {
+        self.settings_launched = Some(Instant::now());
+        match self.token_tx.as_ref() {
+            Some(tx) => {
+                let _ = tx.send(TokenRequest {
+                    app_id: Self::APP_ID.to_string(),
+                    exec,
+                });
+            }
+            None => {
+                tracing::info!(exec, "token channel not ready, launching without a token");
+                spawn_exec(&exec, None);
+            }
+        }
+    }
+
+    /// The backgrounds currently shown on the connected outputs.
+    fn shown_backgrounds(&self, config: cosmic_bg_config::Config) -> Vec<Entry> {
+        if config.same_on_all {
+            vec![config.default_background]
+        } else {
+            // Outputs without a wallpaper of their own, like freshly connected ones, show the
+            // default one.
+            let uncovered = self
+                .connected
+                .values()
+                .any(|output| config.backgrounds.iter().all(|e| &e.output != output));
+            let mut backgrounds = config.backgrounds;
+            if uncovered || backgrounds.is_empty() {
+                backgrounds.push(config.default_background);
+            }
+            backgrounds
+        }
+    }
+
+    /// The backgrounds the swatches are extracted from: the one shown on the accent source output
+    /// if it's connected, all of them otherwise.
+    fn accent_backgrounds(&self, backgrounds: Vec<Entry>) -> Vec<Entry> {
+        let Some(output) = self
+            .config
+            .accent_source_output
+            .as_ref()
+            .filter(|&output| self.connected.values().any(|o| o == output))
+        else {
+            return backgrounds;
+        };
+        // An output without a wallpaper of its own shows the default one.
+        let own = backgrounds.iter().position(|e| &e.output == output);
+        let shown = own.or_else(|| backgrounds.iter().position(|e| e.output == "all"));
+        match shown {
+            Some(i) => vec![backgrounds[i].clone()],
+            None => backgrounds,
+        }
+    }
+
+    /// The scheduled event that comes first, the slideshow check or the mode switch, and the
+    /// time left until it.
+    fn next_event(&self) -> Option<(ScheduledEvent, Duration)> {
+        if self.paused {
+            return None;
+        }
+        let check = self
+            .config
+            .slideshow_poll_secs
+            .filter(|&secs| secs > 0)
+            .zip(self.last_poll)
+            .map(|(secs, last_poll)| {
+                let next = last_poll + Duration::from_secs(secs);
+                let left = next.saturating_duration_since(Instant::now());
+                (ScheduledEvent::SlideshowCheck, left)
+            });
+        let switch = self
+            .next_switch
+            .filter(|_| self.config.schedule_enabled)
+            .map(|at| {
+                let left = (at - Local::now()).to_std().unwrap_or_default();
+                (
+                    ScheduledEvent::Switch(self.config.scheduled_is_dark(at)),
+                    left,
+                )
+            });
+        check
+            .into_iter()
+            .chain(switch)
+            .min_by_key(|&(_, left)| left)
+    }
+
+    /// Recomputes the colors if the shown wallpapers changed since they were last extracted.
+    fn poll_slideshow(&mut self, context: &Context) -> Task<cosmic::Action<Message>> {
+        let Ok(config) = load_bg_config(context) else {
+            return Task::none();
+        };
+        let sources: Vec<_> = self
+            .shown_backgrounds(config)
+            .into_iter()
+            .map(|e| e.source)
+            .collect_unique();
+        if sources != self.sources {
+            return self.update_colors(context);
+        }
+        Task::none()
+    }
+
+    /// Recomputes the swatches from the shown wallpapers. The colors of images that aren't cached
+    /// yet are extracted off the main thread first, and the current swatches stay until then.
+    fn update_colors(&mut self, context: &Context) -> Task<cosmic::Action<Message>> {
+        // Any extraction still running is for wallpapers that may no longer be shown.
+        self.extraction_generation += 1;
+        let Ok(config) = load_bg_config(context) else {
+            return Task::none();
+        };
+        let backgrounds = self.shown_backgrounds(config);
+
+        self.sources = backgrounds
+            .iter()
+            .map(|e| e.source.clone())
+            .collect_unique();
+        self.label = self
+            .sources
+            .iter()
+            .map(source_label)
+            .collect::<Vec<_>>()
+            .join(", ");
+        if !self.config.extract_colors {
+            self.colors.clear();
+            self.average = None;
+            return Task::none();
+        }
+        let extraction = self.config.extraction();
+        let backgrounds = self.accent_backgrounds(backgrounds);
+        let missing = backgrounds
+            .iter()
+            .filter_map(|e| match &e.source {
+                Source::Path(path) if !self.cache.has(path, extraction) => Some(path.clone()),
+                _ => None,
+            })
+            .collect_unique::<Vec<_>>();
+        if !missing.is_empty() {
+            // The generation tells results for superseded wallpapers apart.
+            let generation = self.extraction_generation;
+            return self.extract_colors(missing, extraction, move |colors| {
+                Message::ColorsComputed(generation, extraction, colors)
+            });
+        }
+        let started = Instant::now();
+        let log_timings = self.config.log_timings;
+        // Every background contributes, so that colors shown on several outputs count more.
+        let mut swatches = backgrounds
+            .into_iter()
+            .map(|e| e.source)
+            .flat_map(|source| match source {
+                Source::Path(path_buf) => {
+                    // The original path is what cosmic-bg keeps, the cache resolves symlinks.
+                    let mut colors = self.cache.get(&path_buf, extraction).unwrap_or_default();
+                    // The cache keeps them all, so changing the count needs no new extraction.
+                    colors.truncate(self.config.color_count.into());
+                    colors
+                }
+                Source::Color(cosmic_bg_config::Color::Gradient(_))
+                    if !self.config.include_gradient_colors =>
+                {
+                    vec![]
+                }
+                Source::Color(color) if self.config.use_bg_color => vec![bg_color(&color)],
+                Source::Color(color) => match color {
+                    cosmic_bg_config::Color::Single([r, g, b]) => {
+                        let color = Color::from_rgb(r, g, b);
+                        let count = self.config.shade_count.min(self.config.color_count);
+                        let mut shades = colors::shades(color, count);
+                        if self.config.include_base_color {
+                            // Between the lighter shades and the darker ones.
+                            shades.insert(count.div_ceil(2).into(), color);
+                        }
+                        shades
+                    }
+                    cosmic_bg_config::Color::Gradient(gradient) => {
+                        let mut colors = gradient
+                            .colors
+                            .iter()
+                            .map(|&color| color.into())
+                            .collect::<Vec<_>>();
+                        if !self.config.include_gradient_average {
+                            return colors;
+                        }
+                        gradient
+                            .colors
+                            .windows(2)
+                            .flat_map(|colors| {
+                                colors
+                                    .iter()
+                                    .map(|&color| Srgb::from(color))
+                                    .reduce(|l, r| l.mix(r, 0.5))
+                            })
+                            .for_each(|color| colors.push(color.into()));
+                        colors
+                    }
+                },
+            })
+            .collect_counted();
+        swatches.sort_by(|(_, l), (_, r)| r.cmp(l));
+        self.average = colors::weighted_average(&swatches);
+        let swatches = swatches
+            .into_iter()
+            .map(|(color, _)| color)
+            .collect::<Vec<_>>();
+        let swatches = colors::unique_swatches(swatches, self.config.color_quantize_bits);
+        self.colors = swatches.into_iter().collect_unique_by(|&l, &r| {
+            colors::similar(
+                l,
+                r,
+                self.config.color_metric,
+                self.config.color_merge_distance,
+            )
+        });
+        self.colors = colors::within_luminance(
+            std::mem::take(&mut self.colors),
+            self.config.min_swatch_luminance,
+            self.config.max_swatch_luminance,
+        );
+        // Merging keeps the most frequent of similar swatches, the order shown is another one.
+        sort_swatches(&mut self.colors, self.config.swatch_sort);
+        if log_timings {
+            let (hits, misses) = self.cache.stats();
+            let elapsed = started.elapsed();
+            tracing::debug!(?elapsed, hits, misses, "recomputed colors");
+        }
+        self.auto_accent();
+        Task::none()
+    }
+
+    /// Extracts the colors of the images on a blocking thread, delivering them in the message
+    /// `done` makes of them. Images whose colors are cached are skipped, so each is only
+    /// extracted once however it's reached.
+    fn extract_colors(
+        &self,
+        paths: Vec<PathBuf>,
+        extraction: colors::Extraction,
+        done: impl FnOnce(Vec<(PathBuf, Vec<Color>)>) -> Message + Send + 'static,
+    ) -> Task<cosmic::Action<Message>> {
+        let paths = paths
+            .into_iter()
+            .filter(|path| !self.cache.has(path, extraction))
+            .collect_unique::<Vec<_>>();
+        if paths.is_empty() {
+            return Task::done(cosmic::Action::App(done(vec![])));
+        }
+        let log_timings = self.config.log_timings;
+        let requested = paths.clone();
+        let extract = move || {
+            paths
+                .into_iter()
+                .map(|path| {
+                    let started = Instant::now();
+                    let colors = dominant_colors(path.clone(), extraction);
+                    if log_timings {
+                        let elapsed = started.elapsed();
+                        tracing::debug!(?path, ?elapsed, "extracted colors");
+                    }
+                    (path, colors)
+                })
+                .collect::<Vec<_>>()
+        };
+        Task::perform(
+            async move {
+                tokio::task::spawn_blocking(extract)
+                    .await
+                    .unwrap_or_else(|why| {
+                        tracing::error!(%why, "color extraction failed");
+                        // Caching no colors keeps the wallpapers from being retried forever.
+                        requested.into_iter().map(|path| (path, vec![])).collect()
+                    })
+            },
+            move |colors| cosmic::Action::App(done(colors)),
+        )
+    }
+
+    /// The overall color of the shown wallpapers, the mean of their swatches weighted by how
+    /// often each occurs.
+    pub fn average_color(&self) -> Option<Color> {
+        self.average
+    }
+
+    /// Applies an automatically selected accent, unless one was picked by hand.
+    fn auto_accent(&mut self) {
+        // A remembered accent takes precedence over picking one.
+        if !self.config.auto_accent
+            || self.accent.is_some()
+            || !self.remembered_accents(&self.bg.entries).is_empty()
+        {
+            return;
+        }
+        // Colors close to the overall one would get lost against the wallpaper.
+        let candidates = match self.average_color() {
+            Some(average) => self
+                .colors
+                .iter()
+                .copied()
+                .filter(|&color| {
+                    colors::contrast_ratio(color, average) >= colors::MIN_WALLPAPER_CONTRAST
+                })
+                .collect(),
+            None => vec![],
+        };
+        let candidates = if candidates.is_empty() {
+            &self.colors
+        } else {
+            &candidates
+        };
+        let color = match self.config.pinned_hue {
+            Some(hue) => colors::nearest_hue(candidates, hue),
+            None => colors::select_accent(candidates, self.config.accent_temperature_bias),
+        };
+        let Some(color) = color else {
+            return;
+        };
+        if let Err(why) = self.apply_accent(color, self.core.system_theme_mode().is_dark, None) {
+            tracing::error!(%why, "failed to apply accent color");
+        }
+    }
+}
+
+/// What `AppModel::apply_mode` changed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Applied {
+    pub mode_changed: bool,
+    /// The number of wallpaper entries applied.
+    pub wallpapers: usize,
+    pub accent: Option<Color>,
+}
+
+/// Something the applet does by itself at a scheduled time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScheduledEvent {
+    SlideshowCheck,
+    /// The schedule switches to dark mode if true, to light mode otherwise.
+    Switch(bool),
+}
+
+/// The color of the theme a write changes, the other being left as it is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ThemeField {
+    Accent,
+    WindowHint,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    TogglePopup,
+    PopupClosed(Id),
+    ConfigUpdate(Config),
+    BgUpdate(Bg),
+    Toggle(bool, bool),
+    OpenSettings,
+    OpenModeSettings(bool),
+    /// Applies the accent to the theme of the output, or of all outputs.
+    ChangeAccentColor(Color, Option<String>),
+    PickSwatch(Color),
+    SetBlend(f32),
+    /// Applies the window hint alone, or makes it follow the accent again.
+    ChangeWindowHint(Option<Color>),
+    /// Puts back the accent and window hint the theme had before the applet changed them.
+    RevertAccent,
+    SetPickWindowHint(bool),
+    SetAccentTarget(bool),
+    SetVision(usize),
+    Token(TokenUpdate),
+    OutputAdded(WlOutput, String, Option<String>),
+    OutputRemoved(WlOutput),
+    ToggleHistory,
+    ToggleSwatches,
+    PreviewMode(bool),
+    UndoPreview,
+    OpenAppletSettings,
+    CloseAppletSettings,
+    Set(Setting),
+    EditText(TextSetting, String),
+    SubmitText(TextSetting),
+    SetScalingMode(bool, usize),
+    ToggleOutputs,
+    Scrolled(AbsoluteOffset),
+    CopyPalette,
+    RandomAccent,
+    AdjustSaturation(f32),
+    AdjustLightness(f32),
+    ToggleWindowHint(bool),
+    ToggleManageTheme(bool),
+    ApplyHistoryAccent(usize, Color),
+    PollSlideshow,
+    /// Checks the clock against the light and dark mode schedule.
+    CheckSchedule,
+    /// Redraws the popup, so the time left until the next event stays current.
+    Tick,
+    PauseAutomation(bool),
+    ResumeAutomation(u64),
+    ModeSettled(u64, bool),
+    AccentStep {
+        animation: u64,
+        is_dark: bool,
+        output: Option<String>,
+        color: Color,
+        last: bool,
+    },
+    ThemeBuilderUpdate(bool, ThemeBuilder),
+    PickModeColor(bool),
+    ColorPicker(ColorPickerUpdate),
+    RequestReset,
+    ImportPalette,
+    SaveSnapshot,
+    LoadSnapshot,
+    SnapshotLoaded(Option<Snapshot>),
+    PaletteImported(Vec<Color>),
+    DismissImported,
+    /// Problems loading one of the watched configs, along with the update of the config, which
+    /// is still applied.
+    ConfigErrors(Vec<String>, Box<Message>),
+    ToggleErrors,
+    DismissErrors,
+    OpenGallery,
+    GalleryOpened(Option<PathBuf>),
+    /// Loads the next few gallery images and extracts their colors.
+    ExtractGallery,
+    /// The thumbnails of the batch of gallery images that could be loaded, decoded off the main
+    /// thread along with the colors of the ones that weren't cached.
+    GalleryExtracted(
+        u64,
+        colors::Extraction,
+        Vec<(PathBuf, image::Handle, Option<Vec<Color>>)>,
+    ),
+    PickGalleryImage(PathBuf),
+    CloseGallery,
+    Focus(bool),
+    ToggleEntries(bool),
+    /// The thumbnails of the listed wallpapers, decoded off the main thread.
+    ThumbnailsLoaded(Vec<(PathBuf, image::Handle)>),
+    SetColorCount(u8),
+    /// The colors extracted off the main thread for a swatch recomputation.
+    ColorsComputed(u64, colors::Extraction, Vec<(PathBuf, Vec<Color>)>),
+    /// Removes the wallpaper at the index from the mode.
+    RemoveEntry(bool, usize),
+    /// Extracts the swatches from the wallpaper of the output, or of all outputs.
+    SetAccentSource(Option<String>),
+    ConfirmReset(bool),
+}
+
+impl cosmic::Application for AppModel {
+    type Executor = cosmic::executor::Default;
+    type Flags = ();
+    type Message = Message;
+    const APP_ID: &'static str = "com.github.pstroka.BackgroundManager";
+
+    fn core(&self) -> &cosmic::Core {
+        &self.core
+    }
+
+    fn core_mut(&mut self) -> &mut cosmic::Core {
+        &mut self.core
+    }
+
+    fn init(
+        core: cosmic::Core,
+        _flags: Self::Flags,
+    ) -> (Self, Task<cosmic::Action<Self::Message>>) {
+        let mut app = AppModel {
+            core,
+            config_handler: Config::config().ok(),
+            config: Config::config()
+                .map(|context| match Config::get_entry(&context) {
+                    Ok(config) => config,
+                    Err((errors, config)) => {
+                        for why in errors {
+                            tracing::error!(%why, "error loading app config");
+                        }
+
+                        config
+                    }
+                })
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+
+        if let Some(handler) = app.config_handler.as_ref() {
+            if let Err(why) = app.config.migrate(handler) {
+                tracing::error!(%why, "failed to migrate app config");
+            }
+        }
+        app.icon_name = app.config.valid_icon_name();
+        app.theme_accent = current_accent(app.core.system_theme_mode().is_dark);
+        app.scaling_modes = vec![fl!("fit-zoom"), fl!("fit-fit"), fl!("fit-stretch")];
+        app.modes = vec![fl!("mode-dark"), fl!("mode-light")];
+        app.visions = vec![
+            fl!("vision-normal"),
+            fl!("vision-protanopia"),
+            fl!("vision-deuteranopia"),
+            fl!("vision-tritanopia"),
+        ];
+
+        // The cosmic-bg config may not be there yet while the compositor starts, and only shows
+        // up in the watched one after it changes.
+        let bg = Task::perform(wait_for_bg_config(), |config| config)
+            .and_then(|config| Task::done(cosmic::Action::App(Message::BgUpdate(config.into()))));
+        (app, bg)
+    }
+
+    fn on_close_requested(&self, id: Id) -> Option<Message> {
+        Some(Message::PopupClosed(id))
+    }
+
+    fn view(&self) -> Element<'_, Self::Message> {
+        let button = self
+            .core
+            .applet
+            .icon_button(&self.icon_name)
+            .on_press_down(Message::TogglePopup);
+        tooltip(button, text(&self.wallpaper), tooltip::Position::Bottom).into()
+    }
+
+    fn view_window(&self, id: Id) -> Element<'_, Self::Message> {
+        if self.settings_popup == Some(id) {
+            return self
+                .core
+                .applet
+                .popup_container(cosmic::widget::scrollable(self.settings_view()))
+                .into();
+        }
+
+        let undo_preview = self.preview.as_ref().map(|_| {
+            column![
+                menu_button(text(fl!("undo-preview"))).on_press(Message::UndoPreview),
+                padded_control(divider::horizontal::default()),
+            ]
+        });
+        let save_failed = (self.config_handler.is_none() || self.save_failed)
+            .then(|| padded_control(text::caption(fl!("cant-save"))));
+        let paused = self.paused.then(|| {
+            let label = match self.resume_at {
+                Some(at) => fl!(
+                    "automation-paused-until",
+                    time = format_remaining(at.saturating_duration_since(Instant::now()))
+                ),
+                None => fl!("automation-paused"),
+            };
+            column![
+                menu_button(text::heading(label)).on_press(Message::PauseAutomation(false)),
+                padded_control(divider::horizontal::default()),
+            ]
+        });
+        let content_list = column![]
+            .push_maybe(paused)
+            .push_maybe(save_failed)
+            .push_maybe(self.errors_section())
+            .push_maybe(undo_preview)
+            .push_maybe(self.next_event().map(|(event, left)| {
+                let time = format_remaining(left);
+                padded_control(text::caption(match event {
+                    ScheduledEvent::SlideshowCheck => fl!("next-slideshow-check", time = time),
+                    ScheduledEvent::Switch(true) => fl!("next-switch-dark", time = time),
+                    ScheduledEvent::Switch(false) => fl!("next-switch-light", time = time),
+                }))
+            }))
+            .push(column![
+                padded_item(
+                    fl!("switcher-dark"),
+                    toggler(self.config.dark_enabled).on_toggle(|v| Message::Toggle(true, v))
+                ),
+                padded_item(
+                    fl!("switcher-light"),
+                    toggler(self.config.light_enabled).on_toggle(|v| Message::Toggle(false, v))
+                ),
+                padded_item(
+                    fl!("pause-automation"),
+                    toggler(self.paused).on_toggle(Message::PauseAutomation)
+                ),
+                padded_item(
+                    fl!("focus-mode"),
+                    toggler(self.focus.is_some()).on_toggle(Message::Focus)
+                ),
+                padded_control(divider::horizontal::default()),
+                self.mode_item(fl!("settings-dark"), &self.config.dark, true),
+                padded_control(divider::horizontal::default()),
+                self.mode_item(fl!("settings-light"), &self.config.light, false),
+            ])
+            .push_maybe(self.config.shares_wallpapers().then(|| {
+                padded_item(
+                    fl!("link-modes"),
+                    toggler(self.config.link_modes)
+                        .on_toggle(|v| Message::Set(Setting::LinkModes(v))),
+                )
+            }))
+            .push(column![
+                padded_control(divider::horizontal::default()),
+                menu_button(text(fl!("settings"))).on_press(Message::OpenSettings),
+                menu_button(text(fl!("applet-settings"))).on_press(Message::OpenAppletSettings),
+                menu_button(text(fl!("save-snapshot"))).on_press(Message::SaveSnapshot),
+                menu_button(text(fl!("load-snapshot"))).on_press(Message::LoadSnapshot),
+                padded_control(divider::horizontal::default()),
+                padded_item(
+                    fl!("manage-theme"),
+                    toggler(self.config.manage_theme).on_toggle(Message::ToggleManageTheme)
+                ),
+            ])
+            .push_maybe(self.config.manage_theme.then(|| self.accent_section()))
+            .push(padded_control(divider::horizontal::default()))
+            .push(self.outputs_section())
+            .padding([8, 0, 8, 0]);
+
+        let content = cosmic::widget::scrollable(content_list)
+            .id(SCROLLABLE_ID.clone())
+            .on_scroll(|viewport| Message::Scrolled(viewport.absolute_offset()));
+
+        self.core.applet.popup_container(content).into()
+    }
+
+    fn subscription(&self) -> Subscription<Self::Message> {
+        let slideshow = match self.config.slideshow_poll_secs {
+            Some(secs) if secs > 0 && !self.paused => {
+                cosmic::iced::time::every(Duration::from_secs(secs)).map(|_| Message::PollSlideshow)
+            }
+            _ => Subscription::none(),
+        };
+        // The clock is checked rather than a timer set, since a timer doesn't count while
+        // suspended.
+        let schedule = if self.config.schedule_enabled && !self.paused {
+            cosmic::iced::time::every(SCHEDULE_CHECK).map(|_| Message::CheckSchedule)
+        } else {
+            Subscription::none()
+        };
+        let counting = self.next_event().is_some() || self.resume_at.is_some();
+        let countdown = if self.popup.is_some() && counting {
+            cosmic::iced::time::every(Duration::from_secs(1)).map(|_| Message::Tick)
+        } else {
+            Subscription::none()
+        };
+        Subscription::batch(vec![
+            activation_token_subscription(0).map(Message::Token),
+            self.core()
+                .watch_config::<Config>(Self::APP_ID)
+                .map(|update| watched(Self::APP_ID, update, Message::ConfigUpdate)),
+            self.core()
+                .watch_config::<Bg>(cosmic_bg_config::NAME)
+                .map(|update| watched(cosmic_bg_config::NAME, update, Message::BgUpdate)),
+            self.core()
+                .watch_config::<ThemeBuilder>(DARK_THEME_BUILDER_ID)
+                .map(|update| {
+                    watched(DARK_THEME_BUILDER_ID, update, |builder| {
+                        Message::ThemeBuilderUpdate(true, builder)
+                    })
+                }),
+            self.core()
+                .watch_config::<ThemeBuilder>(LIGHT_THEME_BUILDER_ID)
+                .map(|update| {
+                    watched(LIGHT_THEME_BUILDER_ID, update, |builder| {
+                        Message::ThemeBuilderUpdate(false, builder)
+                    })
+                }),
+            event::listen_with(output_event),
+            slideshow,
+            schedule,
+            countdown,
+        ])
+    }
+
+    fn update(&mut self, message: Self::Message) -> Task<cosmic::Action<Self::Message>> {
+        match message {
+            Message::ConfigUpdate(config) => {
+                if config.icon_name != self.config.icon_name {
+                    self.icon_name = config.valid_icon_name();
+                }
+                self.config = config;
+            }
+            Message::ThemeBuilderUpdate(is_dark, builder) => {
+                if is_dark != self.core.system_theme_mode().is_dark {
+                    return Task::none();
+                }
+                let Some(accent) = builder.accent.map(Color::from) else {
+                    return Task::none();
+                };
+                let same = |color: Color| colors::quantize(color, 8) == colors::quantize(accent, 8);
+                if self.animation_writes.iter().any(|&color| same(color)) {
+                    return Task::none();
+                }
+                match self.theme_accent {
+                    Some(seen) if same(seen) => {}
+                    // The accent the theme had when the applet started isn't a choice made
+                    // since, so it shouldn't keep the accent from being picked automatically.
+                    None => self.theme_accent = Some(accent),
+                    Some(_) => {
+                        tracing::debug!(?accent, "accent changed outside the applet");
+                        self.theme_accent = Some(accent);
+                        self.accent = Some(
+                            self.colors
+                                .iter()
+                                .copied()
+                                .find(|&color| same(color))
+                                .unwrap_or(accent),
+                        );
+                        self.adjustment = (0.0, 0.0);
+                    }
+                }
+            }
+            Message::PickModeColor(is_dark) => {
+                let current = self
+                    .config
+                    .entries(is_dark)
+                    .iter()
+                    .find_map(|e| match e.source {
+                        Source::Color(cosmic_bg_config::Color::Single([r, g, b])) => {
+                            Some(Color::from_rgb(r, g, b))
+                        }
+                        _ => None,
+                    });
+                let model =
+                    ColorPickerModel::new(fl!("hex"), fl!("rgb"), None, current.or(self.accent));
+                self.color_picker = Some((is_dark, model));
+            }
+            Message::ColorPicker(update) => {
+                let Some((is_dark, model)) = self.color_picker.as_mut() else {
+                    return Task::none();
+                };
+                let is_dark = *is_dark;
+                match update {
+                    ColorPickerUpdate::AppliedColor => {
+                        let task = model.update(ColorPickerUpdate::AppliedColor);
+                        let color = model.get_applied_color();
+                        self.color_picker = None;
+                        if let Some(color) = color {
+                            self.set_mode_color(is_dark, color);
+                        }
+                        return task;
+                    }
+                    ColorPickerUpdate::Cancel => {
+                        self.color_picker = None;
+                    }
+                    update => return model.update(update),
+                }
+            }
+            Message::AccentStep {
+                animation,
+                is_dark,
+                output,
+                color,
+                last,
+            } => {
+                // A newer accent change took over.
+                if animation != self.accent_animation {
+                    return Task::none();
+                }
+                let result = if last {
+                    self.apply_accent(color, is_dark, output.as_deref())
+                } else {
+                    self.write_theme(color, is_dark, output.as_deref(), ThemeField::Accent)
+                        .map(|written| self.animation_writes.push(written))
+                };
+                if let Err(why) = result {
+                    tracing::error!(%why, "failed to apply accent color");
+                }
+            }
+            Message::ModeSettled(switch, is_dark) => {
+                // Another mode change followed this one.
+                if switch != self.mode_switches {
+                    return Task::none();
+                }
+                let Some(context) = bg_context() else {
+                    return Task::none();
+                };
+                self.update_bg(is_dark, &context);
+                return self.update_colors(&context);
+            }
+            Message::Tick => {}
+            Message::PauseAutomation(paused) => {
+                self.paused = paused;
+                self.pauses += 1;
+                self.resume_at = None;
+                tracing::info!(paused, "automation paused");
+                if let Some(hours) = self.config.pause_resume_hours.filter(|_| paused) {
+                    let after = Duration::from_secs(u64::from(hours) * 3600);
+                    self.resume_at = Some(Instant::now() + after);
+                    let pause = self.pauses;
+                    return Task::perform(tokio::time::sleep(after), move |_| {
+                        cosmic::Action::App(Message::ResumeAutomation(pause))
+                    });
+                }
+            }
+            Message::ResumeAutomation(pause) => {
+                if pause == self.pauses && self.paused {
+                    return self.update(Message::PauseAutomation(false));
+                }
+            }
+            Message::PollSlideshow => {
+                self.last_poll = Some(Instant::now());
+                if let Some(context) = bg_context() {
+                    return self.poll_slideshow(&context);
+                }
+            }
+            Message::CheckSchedule => {
+                let now = Local::now();
+                // A switch missed while suspended happens on the first check after resuming.
+                if self.next_switch.is_some_and(|at| now < at) {
+                    return Task::none();
+                }
+                self.next_switch = self.config.next_scheduled_switch(now);
+                let is_dark = self.config.scheduled_is_dark(now);
+                if is_dark != self.core.system_theme_mode().is_dark {
+                    tracing::info!(is_dark, next = ?self.next_switch, "switching modes on schedule");
+                    if let Err(why) = self.apply_mode(is_dark, None) {
+                        tracing::error!(%why, "failed to switch modes on schedule");
+                    }
+                }
+            }
+            Message::Toggle(is_dark, toggled) => {
+                self.save(|config, handler| config.set_enabled(handler, is_dark, toggled));
+                // Only the wallpapers are re-applied, the accent stays whatever it currently is,
+                // even where one is remembered for them.
+                if is_dark == self.core.system_theme_mode().is_dark {
+                    if let Some(context) = bg_context() {
+                        self.apply_wallpapers(is_dark, &context);
+                    }
+                }
+            }
+            Message::TogglePopup => {
+                return if let Some(p) = self.settings_popup.take() {
+                    destroy_popup(p)
+                } else if let Some(p) = self.popup.take() {
+                    destroy_popup(p)
+                } else {
+                    let new_id = Id::unique();
+                    self.popup.replace(new_id);
+                    self.open_popup(new_id).chain(scrollable::scroll_to(
+                        SCROLLABLE_ID.clone(),
+                        self.scroll_offset,
+                    ))
+                }
+            }
+            Message::PopupClosed(id) => {
+                if self.popup.as_ref() == Some(&id) {
+                    self.popup = None;
+                }
+                if self.settings_popup.as_ref() == Some(&id) {
+                    self.settings_popup = None;
+                }
+            }
+            Message::OpenAppletSettings => {
+                let new_id = Id::unique();
+                self.settings_popup.replace(new_id);
+                let open = self.open_popup(new_id);
+                return match self.popup.take() {
+                    Some(p) => destroy_popup(p).chain(open),
+                    None => open,
+                };
+            }
+            Message::CloseAppletSettings => {
+                self.confirm_reset = false;
+                self.text_drafts.clear();
+                if let Some(p) = self.settings_popup.take() {
+                    return destroy_popup(p);
+                }
+            }
+            Message::Set(setting) => {
+                return self.update_setting(setting);
+            }
+            Message::EditText(setting, text) => {
+                self.text_drafts.insert(setting, text);
+            }
+            Message::SubmitText(setting) => {
+                if let Some(text) = self.text_drafts.remove(&setting) {
+                    return self.update_setting(setting.with(text));
+                }
+            }
+            Message::RequestReset => {
+                self.confirm_reset = true;
+            }
+            Message::ConfirmReset(confirmed) => {
+                self.confirm_reset = false;
+                if confirmed {
+                    return self.reset_settings();
+                }
+            }
+            Message::BgUpdate(config) => {
+                self.bg = config.clone();
+                let Some(default_background) = config.entries.first() else {
+                    return Task::none();
+                };
+                self.wallpaper = match &default_background.source {
+                    Source::Path(path) => path.display().to_string(),
+                    source => source_label(source),
+                };
+                let context = bg_context();
+                let colors = context
+                    .as_ref()
+                    .map_or_else(Task::none, |context| self.update_colors(context));
+                if self.preview.is_some() || self.focus.is_some() {
+                    // The previewed wallpapers belong to the other mode, and the focus wallpaper
+                    // to none.
+                    return colors;
+                }
+                let is_dark = self.core.system_theme_mode().is_dark;
+                self.capture_output_names(&config.entries);
+                if self.should_enforce(is_dark, &config.entries) {
+                    tracing::info!("wallpapers changed outside the applet, reapplying");
+                    if let Some(context) = &context {
+                        self.update_bg(is_dark, context);
+                    }
+                } else {
+                    let entries = self.config.merged_entries(is_dark, &config);
+                    if entries[..] != *self.config.entries(is_dark) {
+                        self.save(|app_config, handler| {
+                            app_config.set_entries(handler, is_dark, entries)
+                        });
+                    }
+                }
+                return colors;
+            }
+            Message::OutputAdded(wl_output, output, name) => {
+                if let Some(name) = name {
+                    self.outputs.insert(output.clone(), name);
+                }
+                if self.connected.insert(wl_output, output).is_none() {
+                    if let Some(context) = bg_context() {
+                        return self.update_colors(&context);
+                    }
+                }
+            }
+            Message::OutputRemoved(wl_output) => {
+                if self.connected.remove(&wl_output).is_some() {
+                    if let Some(context) = bg_context() {
+                        return self.update_colors(&context);
+                    }
+                }
+            }
+            Message::OpenSettings => {
+                self.open_settings(SETTINGS_COMMAND.to_string());
+            }
+            Message::OpenModeSettings(is_dark) => {
+                if let Err(why) = self.apply_mode(is_dark, None) {
+                    tracing::error!(%why, "failed to switch mode");
+                }
+                let exec = self
+                    .config
+                    .settings_command(is_dark)
+                    .unwrap_or(SETTINGS_COMMAND)
+                    .to_string();
+                self.open_settings(exec);
+            }
+            Message::Token(u) => match u {
+                TokenUpdate::Init(tx) => {
+                    self.token_tx = Some(tx);
+                }
+                TokenUpdate::Finished => {
+                    self.token_tx = None;
+                }
+                TokenUpdate::ActivationToken { token, exec } => {
+                    spawn_exec(&exec, token);
+                }
+            },
+            Message::ChangeAccentColor(color, output) => {
+                let wallpaper = self
+                    .active_wallpaper(output.as_deref())
+                    .filter(|_| self.config.remember_accent)
+                    .cloned();
+                let task = self.change_accent(color, self.accent_target(), output);
+                let label = self.label.clone();
+                let palette = self.colors.iter().map(|&c| c.into()).collect();
+                self.save(|config, handler| {
+                    config.record_palette(handler, label, palette, color.into())
+                });
+                if let Some(path) = wallpaper {
+                    self.save(|config, handler| {
+                        config.remember_wallpaper_accent(handler, &path, color.into())
+                    });
+                }
+                return task;
+            }
+            Message::PickSwatch(color) => {
+                if self.config.pick_window_hint && self.config.separate_window_hint {
+                    return self.update(Message::ChangeWindowHint(Some(color)));
+                }
+                let color = match self.accent {
+                    Some(accent) if self.blend > 0.0 => colors::blend(color, accent, self.blend),
+                    _ => color,
+                };
+                return self.update(Message::ChangeAccentColor(color, None));
+            }
+            Message::ChangeWindowHint(hint) => {
+                self.secondary = hint;
+                // Following the accent again takes the accent's color.
+                let Some(color) = hint.or(self.accent).or(self.theme_accent) else {
+                    return Task::none();
+                };
+                if let Err(why) = self.apply_window_hint(color, self.accent_target()) {
+                    tracing::error!(%why, "failed to apply window hint");
+                }
+            }
+            Message::RevertAccent => {
+                // A running fade would write over the reverted accent.
+                self.accent_animation += 1;
+                self.accent = None;
+                self.secondary = None;
+                if let Err(why) = self.revert_theme(self.accent_target()) {
+                    tracing::error!(%why, "failed to revert accent color");
+                }
+            }
+            Message::SetPickWindowHint(enabled) => {
+                self.save(|config, handler| config.set_pick_window_hint(handler, enabled));
+            }
+            Message::SetBlend(blend) => {
+                self.blend = blend;
+            }
+            Message::SetVision(i) => {
+                self.vision = match i {
+                    1 => Some(colors::ColorVision::Protanopia),
+                    2 => Some(colors::ColorVision::Deuteranopia),
+                    3 => Some(colors::ColorVision::Tritanopia),
+                    _ => None,
+                };
+            }
+            Message::SetAccentTarget(is_dark) => {
+                self.accent_target =
+                    (is_dark != self.core.system_theme_mode().is_dark).then_some(is_dark);
+            }
+            Message::ToggleManageTheme(enabled) => {
+                return self.update_setting(Setting::ManageTheme(enabled));
+            }
+            Message::ToggleWindowHint(enabled) => {
+                self.save(|config, handler| config.set_window_hint_enabled(handler, enabled));
+            }
+            Message::RandomAccent => {
+                if let Some(color) = colors::pick_random(&self.colors, self.accent, &mut self.rng) {
+                    return self.update(Message::ChangeAccentColor(color, None));
+                }
+            }
+            Message::CopyPalette => {
+                return cosmic::iced::clipboard::write(colors::palette_to_hex(&self.colors));
+            }
+            Message::SaveSnapshot => {
+                let is_dark = self.core.system_theme_mode().is_dark;
+                let snapshot = Snapshot::new(
+                    is_dark,
+                    self.config.entries(is_dark).to_vec(),
+                    self.accent.or(self.theme_accent).map(Into::into),
+                );
+                tokio::spawn(save_snapshot(snapshot));
+            }
+            Message::LoadSnapshot => {
+                return Task::perform(load_snapshot(), |snapshot| {
+                    cosmic::Action::App(Message::SnapshotLoaded(snapshot))
+                });
+            }
+            Message::SnapshotLoaded(snapshot) => {
+                let Some(Snapshot {
+                    is_dark,
+                    entries,
+                    accent,
+                    ..
+                }) = snapshot
+                else {
+                    return Task::none();
+                };
+                self.save(|config, handler| config.set_entries(handler, is_dark, entries));
+                if is_dark == self.core.system_theme_mode().is_dark {
+                    if let Some(context) = bg_context() {
+                        self.update_bg(is_dark, &context);
+                    }
+                }
+                if let Some(accent) = accent {
+                    return self.change_accent(accent.into(), is_dark, None);
+                }
+            }
+            Message::ImportPalette => {
+                return Task::perform(import_scheme(), |colors| {
+                    cosmic::Action::App(Message::PaletteImported(colors))
+                });
+            }
+            Message::PaletteImported(colors) => {
+                if !colors.is_empty() {
+                    self.imported = colors;
+                }
+            }
+            Message::DismissImported => {
+                self.imported.clear();
+            }
+            Message::ConfigErrors(errors, update) => {
+                for why in errors {
+                    tracing::error!(%why, "config error");
+                    if !self.config_errors.contains(&why) {
+                        self.config_errors.push(why);
+                    }
+                }
+                let excess = self.config_errors.len().saturating_sub(MAX_CONFIG_ERRORS);
+                self.config_errors.drain(..excess);
+                return self.update(*update);
+            }
+            Message::ToggleErrors => {
+                self.errors_expanded = !self.errors_expanded;
+            }
+            Message::DismissErrors => {
+                self.config_errors.clear();
+                self.errors_expanded = false;
+            }
+            Message::OpenGallery => {
+                return Task::perform(open_file(fl!("open-gallery"), true), |dir| {
+                    cosmic::Action::App(Message::GalleryOpened(dir))
+                });
+            }
+            Message::GalleryOpened(dir) => {
+                let Some(dir) = dir else {
+                    return Task::none();
+                };
+                let mut paths = match std::fs::read_dir(&dir) {
+                    Ok(entries) => entries
+                        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                        .filter(|path| path.is_file() && ImageFormat::from_path(path).is_ok())
+                        .collect::<Vec<_>>(),
+                    Err(why) => {
+                        tracing::error!(%why, ?dir, "failed to read gallery directory");
+                        return Task::none();
+                    }
+                };
+                paths.sort();
+                // Extraction pops the paths off the end, so they're reversed to go in order.
+                paths.reverse();
+                self.gallery.clear();
+                self.gallery_pending = paths;
+                self.gallery_generation += 1;
+                return self.update(Message::ExtractGallery);
+            }
+            Message::ExtractGallery => {
+                let at = self.gallery_pending.len().saturating_sub(GALLERY_BATCH);
+                let mut batch = self.gallery_pending.split_off(at);
+                batch.reverse();
+                return self.load_gallery(batch, self.config.extraction());
+            }
+            Message::GalleryExtracted(generation, extraction, images) => {
+                let images = images
+                    .into_iter()
+                    .map(|(path, thumbnail, colors)| {
+                        if let Some(colors) = colors {
+                            self.cache.insert(&path, extraction, colors);
+                        }
+                        (path, thumbnail)
+                    })
+                    .collect();
+                // The gallery was closed or another one opened in the meantime.
+                if generation != self.gallery_generation {
+                    return Task::none();
+                }
+                let images = self.gallery_colors(images, extraction);
+                self.gallery.extend(images);
+                if !self.gallery_pending.is_empty() {
+                    return Task::done(cosmic::Action::App(Message::ExtractGallery));
+                }
+            }
+            Message::PickGalleryImage(path) => {
+                self.set_mode_source(self.core.system_theme_mode().is_dark, Source::Path(path));
+            }
+            Message::CloseGallery => {
+                self.gallery.clear();
+                self.gallery_pending.clear();
+                self.gallery_generation += 1;
+            }
+            Message::AdjustSaturation(saturation) => {
+                self.adjust_accent(saturation, self.adjustment.1);
+            }
+            Message::AdjustLightness(lightness) => {
+                self.adjust_accent(self.adjustment.0, lightness);
+            }
+            Message::Scrolled(offset) => {
+                self.scroll_offset = offset;
+            }
+            Message::SetAccentSource(output) => {
+                if self
+                    .save(|config, handler| config.set_accent_source_output(handler, output))
+                    .is_some()
+                {
+                    if let Some(context) = bg_context() {
+                        return self.update_colors(&context);
+                    }
+                }
+            }
+            Message::ToggleOutputs => {
+                self.outputs_expanded = !self.outputs_expanded;
+            }
+            Message::SetScalingMode(is_dark, i) => {
+                let scaling_mode = match i {
+                    0 => ScalingMode::Zoom,
+                    1 => self
+                        .config
+                        .entries(is_dark)
+                        .iter()
+                        .find_map(|e| match e.scaling_mode {
+                            ScalingMode::Fit(color) => Some(ScalingMode::Fit(color)),
+                            _ => None,
+                        })
+                        .unwrap_or(ScalingMode::Fit([0.0, 0.0, 0.0])),
+                    _ => ScalingMode::Stretch,
+                };
+                self.save(|config, handler| {
+                    config.set_scaling_mode(handler, is_dark, scaling_mode)
+                });
+                let current = self.core.system_theme_mode().is_dark;
+                if is_dark == current || self.config.link_modes {
+                    if let Some(context) = bg_context() {
+                        self.update_bg(current, &context);
+                    }
+                }
+            }
+            Message::PreviewMode(is_dark) => {
+                if self.preview.is_none() {
+                    self.preview = Some(self.bg.entries.clone());
+                    if let Some(context) = bg_context() {
+                        self.config
+                            .backend
+                            .apply(&context, self.config.entries(is_dark));
+                    }
+                }
+            }
+            Message::SetColorCount(count) => {
+                if self
+                    .save(|config, handler| config.set_color_count(handler, count))
+                    .is_some_and(|changed| changed)
+                {
+                    if let Some(context) = bg_context() {
+                        return self.update_colors(&context);
+                    }
+                }
+            }
+            Message::ColorsComputed(generation, extraction, colors) => {
+                // Even results for superseded wallpapers are still worth caching.
+                for (path, colors) in colors {
+                    self.cache.insert(&path, extraction, colors);
+                }
+                if generation == self.extraction_generation {
+                    if let Some(context) = bg_context() {
+                        return self.update_colors(&context);
+                    }
+                }
+            }
+            Message::ToggleEntries(is_dark) => {
+                if self.entries_expanded == Some(is_dark) {
+                    self.entries_expanded = None;
+                } else {
+                    self.entries_expanded = Some(is_dark);
+                    return self.load_thumbnails(is_dark);
+                }
+            }
+            Message::ThumbnailsLoaded(thumbnails) => {
+                self.thumbnails.extend(thumbnails);
+            }
+            Message::RemoveEntry(is_dark, i) => {
+                let mut entries = self.config.entries(is_dark).to_vec();
+                if i >= entries.len() {
+                    return Task::none();
+                }
+                entries.remove(i);
+                if self
+                    .save(|config, handler| config.set_entries(handler, is_dark, entries))
+                    .is_some()
+                    && is_dark == self.core.system_theme_mode().is_dark
+                {
+                    if let Some(context) = bg_context() {
+                        self.update_bg(is_dark, &context);
+                    }
+                }
+            }
+            Message::Focus(focus) => {
+                let Some(context) = bg_context() else {
+                    return Task::none();
+                };
+                if focus && self.focus.is_none() {
+                    self.enter_focus(&context);
+                } else if !focus {
+                    self.leave_focus(&context);
+                }
+            }
+            Message::UndoPreview => {
+                if let Some(entries) = self.preview.take() {
+                    if let Some(context) = bg_context() {
+                        self.config.backend.apply(&context, &entries);
+                    }
+                }
+            }
+            Message::ToggleSwatches => {
+                self.swatches_expanded = !self.swatches_expanded;
+            }
+            Message::ToggleHistory => {
+                self.history_expanded = !self.history_expanded;
+            }
+            Message::ApplyHistoryAccent(i, color) => {
+                let task = self.change_accent(color, self.accent_target(), None);
+                if let Some(record) = self.config.history.get(i).cloned() {
+                    self.save(|config, handler| {
+                        config.record_palette(handler, record.label, record.colors, color.into())
+                    });
+                }
+                return task;
+            }
+        }
+        Task::none()
+    }
+
+    fn style(&self) -> Option<cosmic::iced_runtime::Appearance> {
+        Some(cosmic::applet::style())
+    }
+
+    fn system_theme_mode_update(
+        &mut self,
+        _keys: &[&'static str],
+        new_theme: &cosmic::cosmic_theme::ThemeMode,
+    ) -> Task<cosmic::Action<Self::Message>> {
+        // Switching modes replaces any previewed wallpapers, and ends focus mode too. What focus
+        // mode replaced is restored to the mode it was taken from, before the new mode's
+        // wallpapers are applied.
+        self.preview = None;
+        if self.focus.is_some() {
+            match context() {
+                Ok(context) => self.leave_focus(&context),
+                Err(why) => {
+                    tracing::error!(%why, "failed to restore wallpapers after focus mode");
+                    self.focus = None;
+                }
+            }
+        }
+        // The theme of the other mode has an accent of its own.
+        self.theme_accent = current_accent(new_theme.is_dark);
+        self.accent_target = None;
+        self.mode_switches += 1;
+        let switch = self.mode_switches;
+        let is_dark = new_theme.is_dark;
+        let delay = Duration::from_millis(self.config.mode_switch_delay_ms);
+        Task::perform(tokio::time::sleep(delay), move |_| {
+            cosmic::Action::App(Message::ModeSettled(switch, is_dark))
+        })
+    }
+}
+
+fn padded_item<'a, Message: 'static>(
+    title: impl Into<Cow<'a, str>> + 'a,
+    widget: impl Into<Element<'a, Message>> + 'a,
+) -> cosmic::widget::Container<'a, Message, cosmic::Theme> {
+    padded_control(item_row(vec![
+        text(title)
+            .wrapping(Wrapping::Word)
             .width(Length::Fill)
             .align_y(Alignment::Center)
             .into(),
@@ -342,22 +2516,745 @@ fn padded_item<'a, Message: 'static>(
     ]))
 }
 
-fn dominant_colors(path: PathBuf) -> Vec<Color> {
-    if let Some((_, thumbnail, _)) = load_image_with_thumbnail(path) {
-        let pixels = thumbnail
-            .pixels()
-            .flat_map(|p| p.to_rgb().0)
-            .collect::<Vec<_>>();
-        let a = dominant_color::get_colors_with_config(
-            &pixels,
-            false,
-            (thumbnail.width() * thumbnail.height()).into(),
-            0.001,
-        );
-        a.chunks_exact(3)
-            .map(|s| color!(s[0], s[1], s[2]))
-            .collect()
+/// A short description of the time left, like `2h 13m`, `4m 10s` or `8s`.
+fn format_remaining(left: Duration) -> String {
+    let secs = left.as_secs();
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m {s}s"),
+        (h, m, _) => format!("{h}h {m}m"),
+    }
+}
+
+/// The message for an update of a watched config, wrapped with the problems it was loaded with
+/// if there were any. The values that failed to load keep their defaults, the rest still syncs.
+fn watched<T>(
+    id: &str,
+    update: cosmic_config::Update<T>,
+    message: impl FnOnce(T) -> Message,
+) -> Message {
+    let errors = update
+        .errors
+        .iter()
+        .map(|why| format!("{id}: {why}"))
+        .collect::<Vec<_>>();
+    let message = message(update.config);
+    if errors.is_empty() {
+        message
+    } else {
+        Message::ConfigErrors(errors, Box::new(message))
+    }
+}
+
+fn output_event(event: Event, _status: event::Status, _id: Id) -> Option<Message> {
+    match event {
+        Event::PlatformSpecific(PlatformSpecific::Wayland(wayland::Event::Output(
+            wayland::OutputEvent::Created(Some(info)) | wayland::OutputEvent::InfoUpdate(info),
+            wl_output,
+        ))) => {
+            let output = info.name?;
+            let name = info
+                .description
+                .filter(|d| !d.is_empty())
+                .unwrap_or_else(|| format!("{} {}", info.make, info.model));
+            let name = name.trim();
+            let name = (!name.is_empty()).then(|| name.to_string());
+            Some(Message::OutputAdded(wl_output, output, name))
+        }
+        Event::PlatformSpecific(PlatformSpecific::Wayland(wayland::Event::Output(
+            wayland::OutputEvent::Removed,
+            wl_output,
+        ))) => Some(Message::OutputRemoved(wl_output)),
+        _ => None,
+    }
+}
+
+/// The accent the theme builder of the mode currently has.
+fn current_accent(is_dark: bool) -> Option<Color> {
+    let config = if is_dark {
+        ThemeBuilder::dark_config()
+    } else {
+        ThemeBuilder::light_config()
+    };
+    let builder = match ThemeBuilder::get_entry(&config.ok()?) {
+        Ok(builder) => builder,
+        Err((errors, builder)) => {
+            for why in errors {
+                tracing::warn!(%why, "error loading theme builder");
+            }
+            builder
+        }
+    };
+    builder.accent.map(Color::from)
+}
+
+/// Writes the builder, then the theme built from it, returning that theme. If the theme can't be
+/// written, both are rolled back to `previous`, so they never disagree.
+fn write_builder_to(
+    (builder_config, theme_config): &(&cosmic_config::Config, &cosmic_config::Config),
+    is_dark: bool,
+    key: &str,
+    value: impl std::fmt::Debug,
+    previous: &ThemeBuilder,
+    builder: &ThemeBuilder,
+) -> Result<Theme, cosmic_config::Error> {
+    let mode = if is_dark { "dark" } else { "light" };
+    logged_write(&format!("theme-builder/{mode}/{key}"), &value, || {
+        builder.write_entry(builder_config)
+    })?;
+    let theme = builder.clone().build();
+    let written = logged_write(&format!("theme/{mode}/{key}"), &value, || {
+        theme.write_entry(theme_config)
+    });
+    if let Err(why) = written {
+        tracing::error!(%why, key, "failed to write theme, rolling back");
+        // The theme may have been written in part, so it's rebuilt from the old builder too.
+        let rolled_back = previous
+            .write_entry(builder_config)
+            .and_then(|()| previous.clone().build().write_entry(theme_config));
+        if let Err(why) = rolled_back {
+            tracing::error!(%why, key, "failed to roll back the theme");
+        }
+        return Err(why);
+    }
+    Ok(theme)
+}
+
+/// The theme builder and theme configs of the mode an accent for the output goes to. COSMIC has
+/// no per-output themes yet, so every output falls back to the global theme of the mode.
+fn theme_configs(
+    is_dark: bool,
+    output: Option<&str>,
+) -> Result<(cosmic_config::Config, cosmic_config::Config), cosmic_config::Error> {
+    if let Some(output) = output {
+        tracing::debug!(output, "no per-output themes, using the global one");
+    }
+    if is_dark {
+        Ok((ThemeBuilder::dark_config()?, Theme::dark_config()?))
+    } else {
+        Ok((ThemeBuilder::light_config()?, Theme::light_config()?))
+    }
+}
+
+/// Lets the user pick a file, or a directory, to open.
+async fn open_file(title: String, directory: bool) -> Option<PathBuf> {
+    let request = SelectedFiles::open_file()
+        .title(&*title)
+        .modal(true)
+        .multiple(false)
+        .directory(directory)
+        .send()
+        .await
+        .and_then(|request| request.response());
+    match request {
+        Ok(files) => files.uris().first().and_then(|uri| uri.to_file_path().ok()),
+        Err(why) => {
+            tracing::error!(%why, "failed to pick a file");
+            None
+        }
+    }
+}
+
+/// Lets the user pick where to save a file.
+async fn save_file(title: String, name: &str) -> Option<PathBuf> {
+    let request = SelectedFiles::save_file()
+        .title(&*title)
+        .current_name(name)
+        .modal(true)
+        .send()
+        .await
+        .and_then(|request| request.response());
+    match request {
+        Ok(files) => files.uris().first().and_then(|uri| uri.to_file_path().ok()),
+        Err(why) => {
+            tracing::error!(%why, "failed to pick a file");
+            None
+        }
+    }
+}
+
+async fn save_snapshot(snapshot: Snapshot) {
+    let snapshot = match snapshot.to_ron() {
+        Ok(snapshot) => snapshot,
+        Err(why) => {
+            tracing::error!(%why, "failed to serialize snapshot");
+            return;
+        }
+    };
+    let Some(path) = save_file(fl!("save-snapshot"), "look.ron").await else {
+        return;
+    };
+    if let Err(why) = tokio::fs::write(&path, snapshot).await {
+        tracing::error!(%why, ?path, "failed to save snapshot");
+    }
+}
+
+async fn load_snapshot() -> Option<Snapshot> {
+    let path = open_file(fl!("load-snapshot"), false).await?;
+    let snapshot = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|why| why.to_string())
+        .and_then(|snapshot| Snapshot::from_ron(&snapshot));
+    match snapshot {
+        Ok(snapshot) => Some(snapshot),
+        Err(why) => {
+            tracing::error!(%why, ?path, "failed to load snapshot");
+            None
+        }
+    }
+}
+
+/// Lets the user pick a color-scheme file and parses its colors.
+async fn import_scheme() -> Vec<Color> {
+    let Some(path) = open_file(fl!("import-palette"), false).await else {
+        return vec![];
+    };
+    match tokio::fs::read_to_string(&path).await {
+        Ok(scheme) => {
+            let colors = colors::parse_scheme(&scheme);
+            if colors.is_empty() {
+                tracing::warn!(?path, "no colors found in the color scheme");
+            }
+            colors
+        }
+        Err(why) => {
+            tracing::error!(%why, ?path, "failed to read the color scheme");
+            vec![]
+        }
+    }
+}
+
+/// Spawns the command line, passing the activation token along if there is one.
+fn spawn_exec(exec: &str, token: Option<String>) {
+    let mut exec = exec.split_whitespace();
+    if let Some(cmd) = exec.next() {
+        let mut cmd = std::process::Command::new(cmd);
+        cmd.args(exec);
+        if let Some(token) = token {
+            cmd.env("XDG_ACTIVATION_TOKEN", &token);
+            cmd.env("DESKTOP_STARTUP_ID", &token);
+        }
+        tokio::spawn(cosmic::process::spawn(cmd));
+    }
+}
+
+fn source_label(source: &Source) -> String {
+    match source {
+        Source::Path(path) => path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string()),
+        Source::Color(cosmic_bg_config::Color::Single(_)) => fl!("source-color"),
+        Source::Color(cosmic_bg_config::Color::Gradient(_)) => fl!("source-gradient"),
+    }
+}
+
+fn swatch<'a>(color: Color, selected: bool, on_press: Message) -> Element<'a, Message> {
+    let button = color_button(Some(on_press), Some(color), Length::Fill);
+    if selected {
+        container(button)
+            .padding(2)
+            .class(cosmic::theme::Container::custom(|theme| container::Style {
+                border: Border {
+                    color: theme.cosmic().on_bg_color().into(),
+                    width: 2.0,
+                    radius: theme.cosmic().corner_radii.radius_s.into(),
+                },
+                ..Default::default()
+            }))
+            .into()
     } else {
-        vec![]
+        button.into()
+    }
+}
+
+/// A swatch with the label drawn over it, in whichever of black or white reads better on it.
+fn labeled_swatch<'a>(color: Color, label: String, on_press: Message) -> Element<'a, Message> {
+    let label = text::monotext(label).class(cosmic::theme::Text::Color(
+        colors::readable_text_color(color),
+    ));
+    button::custom(
+        container(label)
+            .padding([2, 8])
+            .class(cosmic::theme::Container::custom(move |theme| {
+                container::Style {
+                    background: Some(Background::Color(color)),
+                    border: Border {
+                        radius: theme.cosmic().corner_radii.radius_s.into(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }
+            })),
+    )
+    .class(cosmic::theme::Button::Image)
+    .on_press(on_press)
+    .into()
+}
+
+/// Sorts swatches that are ordered from the most to the least frequent in the given order.
+fn sort_swatches(swatches: &mut [Color], sort: colors::SwatchSort) {
+    match sort {
+        colors::SwatchSort::Frequency => {}
+        colors::SwatchSort::Luminance => swatches.sort_by(|&l, &r| {
+            colors::relative_luminance(r).total_cmp(&colors::relative_luminance(l))
+        }),
+        colors::SwatchSort::Hue => swatches.sort_by(|&l, &r| colors::palette_order(l, r)),
+    }
+}
+
+/// The width of the previews of a mode's wallpapers.
+const ENTRY_THUMBNAIL: f32 = 64.0;
+
+/// The width of the gallery thumbnails.
+const GALLERY_THUMBNAIL: f32 = 96.0;
+
+/// The most swatches shown before the rest are collapsed behind a button.
+const MAX_SWATCHES: usize = 16;
+
+/// The maximum number of color stops a linear gradient can hold.
+const MAX_STOPS: usize = 8;
+
+/// A horizontal strip blending the given colors, sorted by hue, into a single gradient.
+fn palette_strip<'a, Message: 'static>(colors: &[Color]) -> Element<'a, Message> {
+    let mut sorted = colors.to_vec();
+    sorted.sort_by(|&l, &r| colors::hue(l).total_cmp(&colors::hue(r)));
+    let stops = sorted.len().min(MAX_STOPS);
+    let step = sorted.len() as f32 / stops.max(1) as f32;
+    let gradient = (0..stops).fold(Linear::new(Radians(FRAC_PI_2)), |gradient, i| {
+        let offset = i as f32 / (stops - 1).max(1) as f32;
+        gradient.add_stop(offset, sorted[(i as f32 * step) as usize])
+    });
+
+    container(Space::new(Length::Fill, Length::Fixed(16.0)))
+        .class(cosmic::theme::Container::custom(move |_| {
+            container::Style {
+                background: Some(Background::Gradient(gradient.into())),
+                border: Border {
+                    radius: 4.0.into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        }))
+        .into()
+}
+
+/// How long extracting the colors of a single wallpaper may take before it's given up on.
+const EXTRACTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many extraction threads may be running at once. Extractions run one after another, so
+/// past the few that run side by side these are ones that timed out and still hang, which can't
+/// be stopped. No more are started until they finish, so they can't pile up.
+const MAX_EXTRACTION_THREADS: usize = 4;
+
+/// The number of extraction threads still running, including the ones given up on.
+static EXTRACTION_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts an extraction thread as running until dropped, which happens even if it panics.
+struct ExtractionThread;
+
+impl ExtractionThread {
+    fn start() -> Option<Self> {
+        EXTRACTION_THREADS
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |running| {
+                (running < MAX_EXTRACTION_THREADS).then_some(running + 1)
+            })
+            .ok()
+            .map(|_| Self)
+    }
+}
+
+impl Drop for ExtractionThread {
+    fn drop(&mut self) {
+        EXTRACTION_THREADS.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// The colors of the image, extracted anew every time. The applet looks them up in
+/// `ColorCache` first.
+///
+/// None if loading or quantizing it panics or takes longer than `EXTRACTION_TIMEOUT`.
+fn dominant_colors(path: PathBuf, extraction: colors::Extraction) -> Vec<Color> {
+    let image = path.clone();
+    bounded_extraction(&path, move || extract_dominant_colors(image, extraction))
+        .unwrap_or_default()
+}
+
+/// Runs the extraction of the image at `path` on a thread of its own, None if it panics or
+/// takes longer than `EXTRACTION_TIMEOUT`. A hanging one is left behind rather than freezing the
+/// applet, and none is started if too many are left behind already.
+fn bounded_extraction<T: Send + 'static>(
+    path: &Path,
+    extract: impl FnOnce() -> T + Send + 'static,
+) -> Option<T> {
+    let Some(running) = ExtractionThread::start() else {
+        tracing::warn!(?path, "too many color extractions hang, skipping wallpaper");
+        return None;
+    };
+    let (tx, rx) = std::sync::mpsc::channel();
+    let spawned = thread::Builder::new()
+        .name("color-extraction".to_string())
+        .spawn(move || {
+            let _running = running;
+            // The receiver may have timed out already, so failing to send is fine.
+            let _ = tx.send(extract());
+        });
+    if let Err(why) = spawned {
+        tracing::error!(%why, "failed to spawn color extraction");
+        return None;
+    }
+    match rx.recv_timeout(EXTRACTION_TIMEOUT) {
+        Ok(extracted) => Some(extracted),
+        Err(RecvTimeoutError::Timeout) => {
+            tracing::warn!(?path, "color extraction timed out, skipping wallpaper");
+            None
+        }
+        // The thread dropped the sender without sending, which only happens if it panicked.
+        Err(RecvTimeoutError::Disconnected) => {
+            tracing::warn!(?path, "color extraction panicked, skipping wallpaper");
+            None
+        }
+    }
+}
+
+fn extract_dominant_colors(path: PathBuf, extraction: colors::Extraction) -> Vec<Color> {
+    let Some((_, thumbnail, _)) = load_image_with_thumbnail(path.clone()) else {
+        return vec![];
+    };
+    thumbnail_colors(&path, &thumbnail, extraction)
+}
+
+/// The thumbnail of a gallery image to show, along with its colors unless `cached`. Both come
+/// from a single decoding of the image, none if it can't be loaded.
+fn gallery_image(
+    path: PathBuf,
+    extraction: colors::Extraction,
+    cached: bool,
+) -> Option<(image::Handle, Option<Vec<Color>>)> {
+    let shown = path.clone();
+    bounded_extraction(&shown, move || {
+        let Some((_, thumbnail, _)) = load_image_with_thumbnail(path.clone()) else {
+            tracing::warn!(?path, "failed to load gallery image");
+            return None;
+        };
+        let colors = (!cached).then(|| thumbnail_colors(&path, &thumbnail, extraction));
+        let (width, height) = thumbnail.dimensions();
+        let handle = image::Handle::from_rgba(width, height, thumbnail.into_raw());
+        Some((handle, colors))
+    })
+    .flatten()
+}
+
+fn thumbnail_colors(
+    path: &Path,
+    thumbnail: &RgbaImage,
+    extraction: colors::Extraction,
+) -> Vec<Color> {
+    if thumbnail.width() == 0 || thumbnail.height() == 0 {
+        tracing::warn!(
+            ?path,
+            "wallpaper thumbnail is empty, skipping color extraction"
+        );
+        return vec![];
+    }
+    let pixels = thumbnail
+        .pixels()
+        .flat_map(|p| p.to_rgb().0)
+        .collect::<Vec<_>>();
+    colors::colors_from_rgb(&pixels, extraction)
+}
+
+/// The color cosmic-bg itself paints for a color source: the color of a single source or the
+/// average of the stops of a gradient. Image sources have no such color and go through
+/// `dominant_colors` instead.
+fn bg_color(color: &cosmic_bg_config::Color) -> Color {
+    match color {
+        cosmic_bg_config::Color::Single(color) => Srgb::from(*color).into(),
+        cosmic_bg_config::Color::Gradient(gradient) => gradient
+            .colors
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| (i, Srgb::from(color)))
+            .reduce(|(_, avg), (i, color)| (i, avg.mix(color, 1.0 / (i + 1) as f32)))
+            .map(|(_, color)| color.into())
+            .unwrap_or(Color::BLACK),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic::iced_winit::graphics::image::image_rs;
+
+    use super::*;
+
+    #[test]
+    fn malformed_image_has_no_colors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("malformed.png");
+        std::fs::write(&path, b"\x89PNG\r\n\x1a\nnot really a png").unwrap();
+        let started = Instant::now();
+        let colors = dominant_colors(
+            path,
+            colors::Extraction::new(colors::Quality::default(), true),
+        );
+        assert!(colors.is_empty());
+        assert!(started.elapsed() < EXTRACTION_TIMEOUT);
+    }
+
+    #[test]
+    fn degenerate_thumbnails_dont_panic() {
+        let extraction = colors::Extraction::new(colors::Quality::default(), true);
+        assert!(colors::colors_from_rgb(&[], extraction).is_empty());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("1x1.png");
+        image_rs::RgbImage::from_pixel(1, 1, image_rs::Rgb([40, 120, 200]))
+            .save(&path)
+            .unwrap();
+        let colors = dominant_colors(path, extraction);
+        assert!(colors.len() <= 1);
+    }
+
+    #[test]
+    fn applied_entries_read_back_equal() {
+        let (_dir, context) = crate::config::temp_bg_context();
+        let entries = vec![
+            Entry::new("all".to_string(), Source::Path(PathBuf::from("/tmp/a.png"))),
+            Entry::new(
+                "DP-1".to_string(),
+                Source::Path(PathBuf::from("/tmp/b.png")),
+            ),
+            Entry::new(
+                "HDMI-1".to_string(),
+                Source::Color(cosmic_bg_config::Color::Single([0.1, 0.2, 0.3])),
+            ),
+        ];
+        let mut app = AppModel {
+            config: Config {
+                dark_enabled: true,
+                dark: entries.clone(),
+                ..Config::default()
+            },
+            ..AppModel::default()
+        };
+        assert_eq!(app.update_bg(true, &context), entries.len());
+        let bg = Bg::load(&context).unwrap();
+        assert_eq!(bg.entries, entries);
+    }
+
+    /// An app enforcing the given dark mode entries.
+    fn enforcing(entries: Vec<Entry>) -> AppModel {
+        AppModel {
+            config: Config {
+                dark_enabled: true,
+                enforce_wallpapers: true,
+                dark: entries,
+                ..Config::default()
+            },
+            ..AppModel::default()
+        }
+    }
+
+    #[test]
+    fn should_enforce_only_when_wallpapers_drifted() {
+        let ours = Entry::new("all".to_string(), Source::Path(PathBuf::from("/tmp/a.png")));
+        let theirs = Entry::new("all".to_string(), Source::Path(PathBuf::from("/tmp/b.png")));
+        let app = enforcing(vec![ours.clone()]);
+        assert!(!app.should_enforce(true, std::slice::from_ref(&ours)));
+        assert!(app.should_enforce(true, std::slice::from_ref(&theirs)));
+        // The light mode it isn't enabled for is left alone.
+        assert!(!app.should_enforce(false, std::slice::from_ref(&theirs)));
+    }
+
+    #[test]
+    fn should_enforce_leaves_intentional_changes() {
+        let ours = Entry::new("all".to_string(), Source::Path(PathBuf::from("/tmp/a.png")));
+        let theirs = [Entry::new(
+            "all".to_string(),
+            Source::Path(PathBuf::from("/tmp/b.png")),
+        )];
+        let mut app = enforcing(vec![ours.clone()]);
+        app.config.enforce_wallpapers = false;
+        assert!(!app.should_enforce(true, &theirs));
+
+        let mut app = enforcing(vec![ours.clone()]);
+        app.paused = true;
+        assert!(!app.should_enforce(true, &theirs));
+
+        // Changes made while the wallpaper settings may still be open are the user's.
+        let mut app = enforcing(vec![ours.clone()]);
+        app.settings_launched = Some(Instant::now());
+        assert!(!app.should_enforce(true, &theirs));
+
+        let mut app = enforcing(vec![ours]);
+        app.focus = Some(Snapshot {
+            version: crate::snapshot::VERSION,
+            is_dark: true,
+            entries: vec![],
+            accent: None,
+        });
+        assert!(!app.should_enforce(true, &theirs));
+    }
+
+    #[test]
+    fn next_event_is_the_sooner_one() {
+        // The default schedule switches to light mode at 7:00.
+        let tomorrow = Local::now().date_naive().succ_opt().unwrap();
+        let at = tomorrow
+            .and_hms_opt(7, 0, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .earliest()
+            .unwrap();
+        let mut app = AppModel {
+            config: Config {
+                schedule_enabled: true,
+                slideshow_poll_secs: Some(60),
+                ..Config::default()
+            },
+            next_switch: Some(at),
+            ..AppModel::default()
+        };
+        assert!(matches!(
+            app.next_event(),
+            Some((ScheduledEvent::Switch(false), _))
+        ));
+
+        app.last_poll = Some(Instant::now());
+        assert!(matches!(
+            app.next_event(),
+            Some((ScheduledEvent::SlideshowCheck, left)) if left <= Duration::from_secs(60)
+        ));
+
+        app.paused = true;
+        assert_eq!(app.next_event(), None);
+    }
+
+    #[test]
+    fn focus_covers_every_output_and_restores_them() {
+        let (_dir, context) = crate::config::temp_bg_context();
+        let shared = Entry::new("all".to_string(), Source::Path(PathBuf::from("/tmp/a.png")));
+        let own = Entry::new(
+            "DP-1".to_string(),
+            Source::Path(PathBuf::from("/tmp/b.png")),
+        );
+        let bg = Bg {
+            entries: vec![shared.clone(), own.clone()],
+            same_on_all: false,
+        };
+        bg.write_to(&context).unwrap();
+        let mut app = AppModel {
+            bg,
+            config: Config {
+                // An output known from the mode, which follows the shared wallpaper for now.
+                dark: vec![Entry::new("HDMI-1".to_string(), shared.source.clone())],
+                light: vec![Entry::new("HDMI-1".to_string(), shared.source.clone())],
+                ..Config::default()
+            },
+            ..AppModel::default()
+        };
+        let shown = |output: &str| {
+            Bg::load(&context)
+                .unwrap()
+                .entries
+                .into_iter()
+                .find(|e| e.output == output)
+                .map(|e| e.source)
+        };
+
+        app.enter_focus(&context);
+        let focused = Source::Color(cosmic_bg_config::Color::Single(FOCUS_WALLPAPER));
+        for output in ["all", "DP-1", "HDMI-1"] {
+            assert_eq!(shown(output), Some(focused.clone()));
+        }
+
+        app.leave_focus(&context);
+        assert_eq!(shown("all"), Some(shared.source.clone()));
+        assert_eq!(shown("DP-1"), Some(own.source));
+        assert_eq!(shown("HDMI-1"), Some(shared.source));
+    }
+
+    #[test]
+    fn toggling_a_mode_keeps_the_picked_accent() {
+        let (_bg_dir, context) = crate::config::temp_bg_context();
+        let (_config_dir, handler) = crate::config::temp_handler();
+        crate::config::use_bg_context(&context);
+        let wallpaper = PathBuf::from("/tmp/a.png");
+        let entries = vec![Entry::new(
+            "all".to_string(),
+            Source::Path(wallpaper.clone()),
+        )];
+        let picked = Color::from_rgb(0.2, 0.4, 0.8);
+        let mut app = AppModel {
+            config_handler: Some(handler),
+            config: Config {
+                dark_enabled: true,
+                light_enabled: true,
+                dark: entries.clone(),
+                light: entries.clone(),
+                auto_accent: true,
+                remember_accent: true,
+                // An accent remembered from before the one picked by hand.
+                wallpaper_accents: std::collections::BTreeMap::from([(
+                    wallpaper,
+                    Srgb::new(0.9, 0.8, 0.1),
+                )]),
+                // Nothing here may touch the theme of the desktop running the tests.
+                manage_theme: false,
+                ..Config::default()
+            },
+            // A more vivid swatch automatic selection would go for.
+            colors: vec![Color::from_rgb(1.0, 0.2, 0.0)],
+            accent: Some(picked),
+            ..AppModel::default()
+        };
+        let is_dark = app.core.system_theme_mode().is_dark;
+        for toggled in [false, true] {
+            let _ = app.update(Message::Toggle(is_dark, toggled));
+            assert_eq!(app.config.enabled(is_dark), toggled);
+            app.auto_accent();
+            assert_eq!(app.accent, Some(picked));
+        }
+        // Toggling the mode back on did apply its wallpapers again.
+        assert_eq!(Bg::load(&context).unwrap().entries, entries);
+    }
+
+    #[test]
+    fn failed_theme_write_rolls_the_builder_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme_path = dir.path().join("theme");
+        let configs = (
+            cosmic_config::Config::with_custom_path(
+                "com.system76.CosmicTheme.Dark.Builder",
+                ThemeBuilder::VERSION,
+                dir.path().join("builder"),
+            )
+            .unwrap(),
+            cosmic_config::Config::with_custom_path(
+                "com.system76.CosmicTheme.Dark",
+                Theme::VERSION,
+                theme_path.clone(),
+            )
+            .unwrap(),
+        );
+        let previous = ThemeBuilder::dark();
+        previous.write_entry(&configs.0).unwrap();
+        // Nothing can be written under a file, so writing the theme fails.
+        std::fs::write(&theme_path, b"").unwrap();
+
+        let app = AppModel {
+            config: Config {
+                manage_theme: true,
+                ..Config::default()
+            },
+            ..AppModel::default()
+        };
+        let accent = Color::from_rgb(0.9, 0.1, 0.5);
+        assert!(app
+            .write_theme_to(&configs, accent, true, ThemeField::Accent)
+            .is_err());
+        let builder = ThemeBuilder::get_entry(&configs.0).unwrap();
+        assert_eq!(builder.accent, previous.accent);
+        assert_eq!(builder.window_hint, previous.window_hint);
     }
 }
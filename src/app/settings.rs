@@ -0,0 +1,641 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! The applet's own settings, shown in a popup of their own.
+
+use std::sync::LazyLock;
+
+use cosmic::applet::{menu_button, padded_control};
+use cosmic::iced_widget::{column, row};
+use cosmic::prelude::*;
+use cosmic::widget::{button, divider, dropdown, slider, text, text_input, toggler};
+
+use super::{padded_item, AppModel, Message};
+use crate::backend::{Backend, WallpaperScope};
+use crate::colors::{self, ColorMetric, Quality, SwatchSort, Temperature};
+use crate::config::{bg_context, ScheduleMode, Time, DEFAULT_QUANTIZE_BITS};
+use crate::fl;
+
+static METRICS: LazyLock<[String; 2]> = LazyLock::new(|| [fl!("metric-cielab"), fl!("metric-rgb")]);
+static SORTS: LazyLock<[String; 3]> = LazyLock::new(|| {
+    [
+        fl!("sort-frequency"),
+        fl!("sort-luminance"),
+        fl!("sort-hue"),
+    ]
+});
+static BACKENDS: LazyLock<[String; 2]> =
+    LazyLock::new(|| [fl!("backend-cosmic-bg"), fl!("backend-portal")]);
+static SCOPES: LazyLock<[String; 3]> = LazyLock::new(|| {
+    [
+        fl!("scope-follow-system"),
+        fl!("scope-shared"),
+        fl!("scope-per-output"),
+    ]
+});
+static QUALITIES: LazyLock<[String; 3]> = LazyLock::new(|| {
+    [
+        fl!("quality-low"),
+        fl!("quality-medium"),
+        fl!("quality-high"),
+    ]
+});
+static SCHEDULE_MODES: LazyLock<[String; 2]> =
+    LazyLock::new(|| [fl!("schedule-manual"), fl!("schedule-solar")]);
+static BIASES: LazyLock<[String; 3]> =
+    LazyLock::new(|| [fl!("bias-none"), fl!("bias-warm"), fl!("bias-cool")]);
+
+/// The default interval when slideshow polling gets enabled.
+const DEFAULT_POLL_SECS: u64 = 60;
+/// The default time until paused automation resumes, when resuming by itself gets enabled.
+const DEFAULT_RESUME_HOURS: u32 = 2;
+
+#[derive(Debug, Clone)]
+pub enum Setting {
+    ExtractColors(bool),
+    UseBgColor(bool),
+    ShadeCount(u8),
+    IncludeBaseColor(bool),
+    IncludeGradientColors(bool),
+    IncludeGradientAverage(bool),
+    ColorMetric(usize),
+    MergeDistance(f32),
+    SwatchSort(usize),
+    MinLuminance(f32),
+    MaxLuminance(f32),
+    QuantizeBits(Option<u8>),
+    DeterministicExtraction(bool),
+    LogTimings(bool),
+    Quality(usize),
+    ColorThreshold(Option<f32>),
+    ManageTheme(bool),
+    HighContrastAccent(bool),
+    GtkAccent(bool),
+    WindowHint(bool),
+    SeparateWindowHint(bool),
+    AnimateAccent(bool),
+    Backend(usize),
+    WallpaperScope(usize),
+    AutoAccent(bool),
+    RememberAccent(bool),
+    TemperatureBias(usize),
+    PinnedHue(Option<f32>),
+    IconName(String),
+    SlideshowPoll(Option<u64>),
+    PauseResume(Option<u32>),
+    Schedule(bool),
+    ScheduleStart(bool, Time),
+    ScheduleMode(usize),
+    Latitude(f32),
+    Longitude(f32),
+    SettingsCommand(bool, String),
+    LinkModes(bool),
+    EnforceWallpapers(bool),
+}
+
+/// A setting typed into a text input, only saved once submitted so that the values in between
+/// are neither written nor validated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextSetting {
+    IconName,
+    /// The command run after switching to the dark mode if true, the light one otherwise.
+    SettingsCommand(bool),
+}
+
+impl TextSetting {
+    /// The setting saving `text`.
+    pub(super) fn with(self, text: String) -> Setting {
+        match self {
+            TextSetting::IconName => Setting::IconName(text),
+            TextSetting::SettingsCommand(is_dark) => Setting::SettingsCommand(is_dark, text),
+        }
+    }
+}
+
+impl Setting {
+    /// Whether the swatches or the accent picked from them can change with this setting.
+    fn recomputes_colors(&self) -> bool {
+        !matches!(
+            self,
+            Setting::IconName(_)
+                | Setting::SettingsCommand(..)
+                | Setting::LogTimings(_)
+                | Setting::AnimateAccent(_)
+                | Setting::Backend(_)
+                | Setting::SlideshowPoll(_)
+                | Setting::PauseResume(_)
+                | Setting::Schedule(_)
+                | Setting::ScheduleStart(..)
+                | Setting::ScheduleMode(_)
+                | Setting::Latitude(_)
+                | Setting::Longitude(_)
+                | Setting::LinkModes(_)
+                | Setting::EnforceWallpapers(_)
+        )
+    }
+}
+
+impl AppModel {
+    pub(super) fn settings_view(&self) -> Element<'_, Message> {
+        let config = &self.config;
+        let quality_threshold = config.quality.threshold();
+        let metric = match config.color_metric {
+            ColorMetric::CieLab => 0,
+            ColorMetric::Rgb => 1,
+        };
+        let sort = match config.swatch_sort {
+            SwatchSort::Frequency => 0,
+            SwatchSort::Luminance => 1,
+            SwatchSort::Hue => 2,
+        };
+        let backend = match config.backend {
+            Backend::CosmicBg => 0,
+            Backend::Portal => 1,
+        };
+        let scope = match config.wallpaper_scope {
+            WallpaperScope::FollowSystem => 0,
+            WallpaperScope::Shared => 1,
+            WallpaperScope::PerOutput => 2,
+        };
+        let quality = match config.quality {
+            Quality::Low => 0,
+            Quality::Medium => 1,
+            Quality::High => 2,
+        };
+        let bias = match config.accent_temperature_bias {
+            None => 0,
+            Some(Temperature::Warm) => 1,
+            Some(Temperature::Cool) => 2,
+        };
+
+        column![
+            padded_item(
+                fl!("setting-extract-colors"),
+                toggler(config.extract_colors)
+                    .on_toggle(|v| Message::Set(Setting::ExtractColors(v)))
+            ),
+            padded_item(
+                fl!("setting-use-bg-color"),
+                toggler(config.use_bg_color).on_toggle(|v| Message::Set(Setting::UseBgColor(v)))
+            ),
+            padded_item(
+                fl!("setting-shade-count", count = config.shade_count),
+                slider(2..=8, config.shade_count, |c| Message::Set(
+                    Setting::ShadeCount(c)
+                ))
+            ),
+            padded_item(
+                fl!("setting-include-base-color"),
+                toggler(config.include_base_color)
+                    .on_toggle(|v| Message::Set(Setting::IncludeBaseColor(v)))
+            ),
+            padded_item(
+                fl!("setting-include-gradient-colors"),
+                toggler(config.include_gradient_colors)
+                    .on_toggle(|v| Message::Set(Setting::IncludeGradientColors(v)))
+            ),
+            padded_item(
+                fl!("setting-include-gradient-average"),
+                toggler(config.include_gradient_average)
+                    .on_toggle(|v| Message::Set(Setting::IncludeGradientAverage(v)))
+            ),
+            padded_item(
+                fl!("setting-color-metric"),
+                dropdown(&*METRICS, Some(metric), |i| Message::Set(
+                    Setting::ColorMetric(i)
+                ))
+            ),
+            padded_item(
+                fl!("setting-swatch-sort"),
+                dropdown(&*SORTS, Some(sort), |i| Message::Set(Setting::SwatchSort(
+                    i
+                )))
+            ),
+            padded_item(
+                fl!(
+                    "setting-merge-distance",
+                    distance = format!("{:.1}", config.color_merge_distance)
+                ),
+                slider(0.0..=10.0, config.color_merge_distance, |d| Message::Set(
+                    Setting::MergeDistance(d)
+                ))
+                .step(0.5)
+            ),
+            padded_item(
+                fl!(
+                    "setting-min-luminance",
+                    luminance = format!("{:.2}", config.min_swatch_luminance)
+                ),
+                slider(0.0..=0.5, config.min_swatch_luminance, |l| Message::Set(
+                    Setting::MinLuminance(l)
+                ))
+                .step(0.01)
+            ),
+            padded_item(
+                fl!(
+                    "setting-max-luminance",
+                    luminance = format!("{:.2}", config.max_swatch_luminance)
+                ),
+                slider(0.5..=1.0, config.max_swatch_luminance, |l| Message::Set(
+                    Setting::MaxLuminance(l)
+                ))
+                .step(0.01)
+            ),
+            padded_item(
+                fl!("setting-quantize"),
+                toggler(config.color_quantize_bits.is_some()).on_toggle(|v| Message::Set(
+                    Setting::QuantizeBits(v.then_some(DEFAULT_QUANTIZE_BITS))
+                ))
+            ),
+        ]
+        .push_maybe(config.color_quantize_bits.map(|bits| {
+            padded_item(
+                fl!("setting-quantize-bits", bits = bits),
+                slider(1..=8, bits, |b| {
+                    Message::Set(Setting::QuantizeBits(Some(b)))
+                }),
+            )
+        }))
+        .push(padded_item(
+            fl!("setting-deterministic"),
+            toggler(config.deterministic_extraction)
+                .on_toggle(|v| Message::Set(Setting::DeterministicExtraction(v))),
+        ))
+        .push(padded_item(
+            fl!("setting-log-timings"),
+            toggler(config.log_timings).on_toggle(|v| Message::Set(Setting::LogTimings(v))),
+        ))
+        .push(padded_item(
+            fl!("setting-quality"),
+            dropdown(&*QUALITIES, Some(quality), |i| {
+                Message::Set(Setting::Quality(i))
+            }),
+        ))
+        .push(padded_item(
+            fl!("setting-color-threshold"),
+            toggler(config.color_threshold.is_some()).on_toggle(move |v| {
+                Message::Set(Setting::ColorThreshold(v.then_some(quality_threshold)))
+            }),
+        ))
+        .push_maybe(config.color_threshold.map(|threshold| {
+            padded_item(
+                fl!(
+                    "setting-color-threshold-value",
+                    percent = format!("{:.2}", threshold * 100.0)
+                ),
+                slider(colors::THRESHOLD_RANGE, threshold, |t| {
+                    Message::Set(Setting::ColorThreshold(Some(t)))
+                })
+                .step(0.0001),
+            )
+        }))
+        .push(padded_control(divider::horizontal::default()))
+        .push(padded_item(
+            fl!("manage-theme"),
+            toggler(config.manage_theme).on_toggle(|v| Message::Set(Setting::ManageTheme(v))),
+        ))
+        .push(padded_item(
+            fl!("setting-high-contrast"),
+            toggler(config.high_contrast_accent)
+                .on_toggle(|v| Message::Set(Setting::HighContrastAccent(v))),
+        ))
+        .push(padded_item(
+            fl!("setting-gtk-accent"),
+            toggler(config.gtk_accent).on_toggle(|v| Message::Set(Setting::GtkAccent(v))),
+        ))
+        .push(padded_item(
+            fl!("window-hint"),
+            toggler(config.window_hint_enabled).on_toggle(|v| Message::Set(Setting::WindowHint(v))),
+        ))
+        .push_maybe(config.window_hint_enabled.then(|| {
+            padded_item(
+                fl!("setting-separate-window-hint"),
+                toggler(config.separate_window_hint)
+                    .on_toggle(|v| Message::Set(Setting::SeparateWindowHint(v))),
+            )
+        }))
+        .push(padded_item(
+            fl!("setting-animate-accent"),
+            toggler(config.animate_accent).on_toggle(|v| Message::Set(Setting::AnimateAccent(v))),
+        ))
+        .push(padded_item(
+            fl!("setting-auto-accent"),
+            toggler(config.auto_accent).on_toggle(|v| Message::Set(Setting::AutoAccent(v))),
+        ))
+        .push(padded_item(
+            fl!("setting-remember-accent"),
+            toggler(config.remember_accent).on_toggle(|v| Message::Set(Setting::RememberAccent(v))),
+        ))
+        .push(padded_item(
+            fl!("setting-temperature-bias"),
+            dropdown(&*BIASES, Some(bias), |i| {
+                Message::Set(Setting::TemperatureBias(i))
+            }),
+        ))
+        .push(padded_item(
+            fl!("setting-pinned-hue"),
+            toggler(config.pinned_hue.is_some())
+                .on_toggle(|v| Message::Set(Setting::PinnedHue(v.then_some(0.0)))),
+        ))
+        .push_maybe(config.pinned_hue.map(|hue| {
+            padded_control(slider(0.0..=359.0, hue, |h| {
+                Message::Set(Setting::PinnedHue(Some(h)))
+            }))
+        }))
+        .push(padded_control(divider::horizontal::default()))
+        .push(padded_item(
+            fl!("setting-backend"),
+            dropdown(&*BACKENDS, Some(backend), |i| {
+                Message::Set(Setting::Backend(i))
+            }),
+        ))
+        .push(padded_item(
+            fl!("setting-scope"),
+            dropdown(&*SCOPES, Some(scope), |i| {
+                Message::Set(Setting::WallpaperScope(i))
+            }),
+        ))
+        .push(padded_item(
+            fl!("setting-enforce"),
+            toggler(config.enforce_wallpapers)
+                .on_toggle(|v| Message::Set(Setting::EnforceWallpapers(v))),
+        ))
+        .push(padded_item(
+            fl!("setting-slideshow"),
+            toggler(config.slideshow_poll_secs.is_some()).on_toggle(|v| {
+                Message::Set(Setting::SlideshowPoll(v.then_some(DEFAULT_POLL_SECS)))
+            }),
+        ))
+        .push_maybe(config.slideshow_poll_secs.map(|secs| {
+            padded_item(
+                fl!("setting-slideshow-secs", secs = secs),
+                slider(10..=600, secs, |s| {
+                    Message::Set(Setting::SlideshowPoll(Some(s)))
+                }),
+            )
+        }))
+        .push(padded_item(
+            fl!("setting-pause-resume"),
+            toggler(config.pause_resume_hours.is_some()).on_toggle(|v| {
+                Message::Set(Setting::PauseResume(v.then_some(DEFAULT_RESUME_HOURS)))
+            }),
+        ))
+        .push_maybe(config.pause_resume_hours.map(|hours| {
+            padded_item(
+                fl!("setting-pause-resume-hours", hours = hours),
+                slider(1..=12, hours, |h| {
+                    Message::Set(Setting::PauseResume(Some(h)))
+                }),
+            )
+        }))
+        .push(padded_item(
+            fl!("setting-schedule"),
+            toggler(config.schedule_enabled).on_toggle(|v| Message::Set(Setting::Schedule(v))),
+        ))
+        .push_maybe(config.schedule_enabled.then(|| {
+            let solar = config.schedule_mode == ScheduleMode::Solar;
+            column![padded_item(
+                fl!("setting-schedule-mode"),
+                dropdown(&*SCHEDULE_MODES, Some(usize::from(solar)), |i| {
+                    Message::Set(Setting::ScheduleMode(i))
+                }),
+            )]
+            .push_maybe(solar.then(|| {
+                column![
+                    padded_item(
+                        fl!(
+                            "setting-latitude",
+                            degrees = format!("{:.1}", config.latitude)
+                        ),
+                        slider(-90.0..=90.0, config.latitude, |v| {
+                            Message::Set(Setting::Latitude(v))
+                        })
+                        .step(0.5),
+                    ),
+                    padded_item(
+                        fl!(
+                            "setting-longitude",
+                            degrees = format!("{:.1}", config.longitude)
+                        ),
+                        slider(-180.0..=180.0, config.longitude, |v| {
+                            Message::Set(Setting::Longitude(v))
+                        })
+                        .step(0.5),
+                    ),
+                    padded_control(text::caption(fl!("solar-fallback"))),
+                ]
+            }))
+            .push(schedule_start(true, config.dark_start))
+            .push(schedule_start(false, config.light_start))
+        }))
+        .push(padded_item(
+            fl!("setting-icon-name"),
+            text_input("", self.draft(TextSetting::IconName, &config.icon_name))
+                .on_input(|v| Message::EditText(TextSetting::IconName, v))
+                .on_submit(|_| Message::SubmitText(TextSetting::IconName)),
+        ))
+        .push(padded_item(
+            fl!("setting-dark-command"),
+            text_input(
+                super::SETTINGS_COMMAND,
+                self.draft(
+                    TextSetting::SettingsCommand(true),
+                    config.dark_settings_command.as_deref().unwrap_or_default(),
+                ),
+            )
+            .on_input(|v| Message::EditText(TextSetting::SettingsCommand(true), v))
+            .on_submit(|_| Message::SubmitText(TextSetting::SettingsCommand(true))),
+        ))
+        .push(padded_item(
+            fl!("setting-light-command"),
+            text_input(
+                super::SETTINGS_COMMAND,
+                self.draft(
+                    TextSetting::SettingsCommand(false),
+                    config.light_settings_command.as_deref().unwrap_or_default(),
+                ),
+            )
+            .on_input(|v| Message::EditText(TextSetting::SettingsCommand(false), v))
+            .on_submit(|_| Message::SubmitText(TextSetting::SettingsCommand(false))),
+        ))
+        .push(padded_control(divider::horizontal::default()))
+        .push(if self.confirm_reset {
+            Element::from(padded_item(
+                fl!("reset-confirm"),
+                row![
+                    button::destructive(fl!("reset")).on_press(Message::ConfirmReset(true)),
+                    button::standard(fl!("cancel")).on_press(Message::ConfirmReset(false)),
+                ]
+                .spacing(8),
+            ))
+        } else {
+            menu_button(text(fl!("reset-settings")))
+                .on_press(Message::RequestReset)
+                .into()
+        })
+        .push(menu_button(text(fl!("close"))).on_press(Message::CloseAppletSettings))
+        .padding([8, 0, 8, 0])
+        .into()
+    }
+
+    /// What was typed into the input of `setting`, or the `saved` value if nothing was.
+    fn draft<'a>(&'a self, setting: TextSetting, saved: &'a str) -> &'a str {
+        self.text_drafts.get(&setting).map_or(saved, String::as_str)
+    }
+
+    pub(super) fn reset_settings(&mut self) -> Task<cosmic::Action<Message>> {
+        self.text_drafts.clear();
+        if self.save(|config, handler| config.reset(handler)) == Some(true) {
+            self.icon_name = self.config.valid_icon_name();
+            if let Some(context) = bg_context() {
+                return self.update_colors(&context);
+            }
+        }
+        Task::none()
+    }
+
+    pub(super) fn update_setting(&mut self, setting: Setting) -> Task<cosmic::Action<Message>> {
+        let icon_changed = matches!(setting, Setting::IconName(_));
+        let recomputes_colors = setting.recomputes_colors();
+        if matches!(
+            setting,
+            Setting::Schedule(_)
+                | Setting::ScheduleStart(..)
+                | Setting::ScheduleMode(_)
+                | Setting::Latitude(_)
+                | Setting::Longitude(_)
+        ) {
+            // The next switch is computed again from the changed schedule.
+            self.next_switch = None;
+        }
+        let changed = self.save(|config, handler| match setting {
+            Setting::ExtractColors(v) => config.set_extract_colors(handler, v),
+            Setting::UseBgColor(v) => config.set_use_bg_color(handler, v),
+            Setting::ShadeCount(count) => config.set_shade_count(handler, count),
+            Setting::IncludeBaseColor(v) => config.set_include_base_color(handler, v),
+            Setting::IncludeGradientColors(v) => config.set_include_gradient_colors(handler, v),
+            Setting::IncludeGradientAverage(v) => config.set_include_gradient_average(handler, v),
+            Setting::MergeDistance(distance) => config.set_color_merge_distance(handler, distance),
+            Setting::MinLuminance(l) => config.set_min_swatch_luminance(handler, l),
+            Setting::MaxLuminance(l) => config.set_max_swatch_luminance(handler, l),
+            Setting::SwatchSort(i) => config.set_swatch_sort(
+                handler,
+                match i {
+                    0 => SwatchSort::Frequency,
+                    1 => SwatchSort::Luminance,
+                    _ => SwatchSort::Hue,
+                },
+            ),
+            Setting::ColorMetric(i) => config.set_color_metric(
+                handler,
+                if i == 1 {
+                    ColorMetric::Rgb
+                } else {
+                    ColorMetric::CieLab
+                },
+            ),
+            Setting::QuantizeBits(bits) => config.set_color_quantize_bits(handler, bits),
+            Setting::DeterministicExtraction(v) => config.set_deterministic_extraction(handler, v),
+            Setting::LogTimings(v) => config.set_log_timings(handler, v),
+            Setting::Quality(i) => config.set_quality(
+                handler,
+                match i {
+                    0 => Quality::Low,
+                    2 => Quality::High,
+                    _ => Quality::Medium,
+                },
+            ),
+            Setting::ColorThreshold(threshold) => config.set_color_threshold(handler, threshold),
+            Setting::ManageTheme(v) => config.set_manage_theme(handler, v),
+            Setting::HighContrastAccent(v) => config.set_high_contrast_accent(handler, v),
+            Setting::GtkAccent(v) => config.set_gtk_accent(handler, v),
+            Setting::WindowHint(v) => config.set_window_hint_enabled(handler, v),
+            Setting::SeparateWindowHint(v) => config.set_separate_window_hint(handler, v),
+            Setting::AnimateAccent(v) => config.set_animate_accent(handler, v),
+            Setting::Backend(i) => config.set_backend(
+                handler,
+                if i == 1 {
+                    Backend::Portal
+                } else {
+                    Backend::CosmicBg
+                },
+            ),
+            Setting::WallpaperScope(i) => config.set_wallpaper_scope(
+                handler,
+                match i {
+                    1 => WallpaperScope::Shared,
+                    2 => WallpaperScope::PerOutput,
+                    _ => WallpaperScope::FollowSystem,
+                },
+            ),
+            Setting::AutoAccent(v) => config.set_auto_accent(handler, v),
+            Setting::RememberAccent(v) => config.set_remember_accent(handler, v),
+            Setting::TemperatureBias(i) => config.set_accent_temperature_bias(
+                handler,
+                match i {
+                    1 => Some(Temperature::Warm),
+                    2 => Some(Temperature::Cool),
+                    _ => None,
+                },
+            ),
+            Setting::PinnedHue(hue) => config.set_pinned_hue(handler, hue),
+            Setting::SlideshowPoll(secs) => config.set_slideshow_poll_secs(handler, secs),
+            Setting::PauseResume(hours) => config.set_pause_resume_hours(handler, hours),
+            Setting::Schedule(v) => config.set_schedule_enabled(handler, v),
+            Setting::ScheduleStart(is_dark, time) => {
+                if is_dark {
+                    config.set_dark_start(handler, time)
+                } else {
+                    config.set_light_start(handler, time)
+                }
+            }
+            Setting::ScheduleMode(i) => config.set_schedule_mode(
+                handler,
+                if i == 1 {
+                    ScheduleMode::Solar
+                } else {
+                    ScheduleMode::Manual
+                },
+            ),
+            Setting::Latitude(degrees) => config.set_latitude(handler, degrees),
+            Setting::Longitude(degrees) => config.set_longitude(handler, degrees),
+            Setting::SettingsCommand(is_dark, command) => {
+                // An empty command falls back to the wallpaper settings.
+                let command = (!command.trim().is_empty()).then_some(command);
+                if is_dark {
+                    config.set_dark_settings_command(handler, command)
+                } else {
+                    config.set_light_settings_command(handler, command)
+                }
+            }
+            Setting::IconName(name) => config.set_icon_name(handler, name),
+            Setting::LinkModes(v) => config.set_link_modes(handler, v),
+            Setting::EnforceWallpapers(v) => config.set_enforce_wallpapers(handler, v),
+        });
+        if icon_changed {
+            self.icon_name = self.config.valid_icon_name();
+        }
+        if recomputes_colors && changed == Some(true) {
+            if let Some(context) = bg_context() {
+                return self.update_colors(&context);
+            }
+        }
+        Task::none()
+    }
+}
+
+/// A slider picking when the schedule switches to the mode, in steps of a quarter hour.
+fn schedule_start<'a>(is_dark: bool, start: Time) -> Element<'a, Message> {
+    let label = if is_dark {
+        fl!("setting-dark-start", time = start.to_string())
+    } else {
+        fl!("setting-light-start", time = start.to_string())
+    };
+    padded_item(
+        label,
+        slider(0..=24 * 60 - 15, start.minutes(), move |minutes| {
+            Message::Set(Setting::ScheduleStart(is_dark, Time::from_minutes(minutes)))
+        })
+        .step(15u32),
+    )
+    .into()
+}
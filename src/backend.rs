@@ -0,0 +1,172 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! The ways the wallpapers of a mode can be applied to the desktop.
+
+use std::fs::File;
+use std::path::PathBuf;
+
+use ashpd::desktop::wallpaper::{SetOn, WallpaperRequest};
+use cosmic_bg_config::{Context, Entry, Source};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{load_bg_config, logged_write, summary};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Backend {
+    /// Writes the entries directly into the cosmic-bg config.
+    #[default]
+    CosmicBg,
+    /// Sets the wallpaper through the `org.freedesktop.portal.Wallpaper` interface. The portal
+    /// only knows about a single image for all outputs, so only the first image entry is used.
+    Portal,
+}
+
+/// Which of a mode's entries are applied.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum WallpaperScope {
+    /// Only the wallpaper shared by all outputs.
+    Shared,
+    /// Only the wallpapers of the individual outputs.
+    PerOutput,
+    /// All the entries, as cosmic-bg had them when they were captured.
+    #[default]
+    FollowSystem,
+}
+
+impl WallpaperScope {
+    /// The entries to apply, given the connector names of the connected outputs.
+    pub fn entries<'a>(
+        self,
+        entries: &[Entry],
+        outputs: impl IntoIterator<Item = &'a String>,
+    ) -> Vec<Entry> {
+        match self {
+            WallpaperScope::FollowSystem => entries.to_vec(),
+            WallpaperScope::Shared => entries
+                .iter()
+                .find(|e| e.output == "all")
+                .or(entries.first())
+                .map(|e| Entry {
+                    output: "all".to_string(),
+                    ..e.clone()
+                })
+                .into_iter()
+                .collect(),
+            WallpaperScope::PerOutput => {
+                let per_output = entries
+                    .iter()
+                    .filter(|e| e.output != "all")
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !per_output.is_empty() {
+                    return per_output;
+                }
+                // Only a shared wallpaper was captured, so every output gets a copy of it.
+                let Some(shared) = entries.first() else {
+                    return vec![];
+                };
+                outputs
+                    .into_iter()
+                    .map(|output| Entry {
+                        output: output.clone(),
+                        ..shared.clone()
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+impl Backend {
+    pub fn apply(self, context: &Context, entries: &[Entry]) {
+        match self {
+            Backend::CosmicBg => {
+                let Ok(mut config) = load_bg_config(context) else {
+                    return;
+                };
+                let changed = entries
+                    .iter()
+                    .filter(|e| needs_write(&config, e))
+                    .collect::<Vec<_>>();
+                for entry in changed {
+                    let key = format!("cosmic-bg/{}", entry.output);
+                    let value = summary(std::slice::from_ref(entry));
+                    if let Err(why) =
+                        logged_write(&key, value, || config.set_entry(context, entry.clone()))
+                    {
+                        tracing::error!(%why, output = entry.output, "failed to set wallpaper");
+                    }
+                }
+            }
+            Backend::Portal => {
+                let path = entries.iter().find_map(|e| match &e.source {
+                    Source::Path(path) => Some(path.clone()),
+                    Source::Color(_) => None,
+                });
+                match path {
+                    Some(path) => {
+                        tokio::spawn(set_portal_wallpaper(path));
+                    }
+                    None => tracing::warn!("the wallpaper portal only supports image wallpapers"),
+                }
+            }
+        }
+    }
+}
+
+/// Whether the entry differs from what cosmic-bg currently has for its output.
+fn needs_write(config: &cosmic_bg_config::Config, entry: &Entry) -> bool {
+    let current = if entry.output == "all" {
+        Some(&config.default_background)
+    } else {
+        config.backgrounds.iter().find(|e| e.output == entry.output)
+    };
+    current != Some(entry)
+}
+
+async fn set_portal_wallpaper(path: PathBuf) {
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(why) => {
+            tracing::error!(%why, ?path, "failed to open wallpaper");
+            return;
+        }
+    };
+    let request = WallpaperRequest::default()
+        .set_on(SetOn::Background)
+        .show_preview(false)
+        .build_file(&file)
+        .await;
+    if let Err(why) = request {
+        tracing::error!(%why, ?path, "failed to set wallpaper through the portal");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::temp_bg_context;
+
+    #[test]
+    fn identical_entries_arent_written_again() {
+        let (_dir, context) = temp_bg_context();
+        let entries = [
+            Entry::new("all".to_string(), Source::Path(PathBuf::from("/tmp/a.png"))),
+            Entry::new(
+                "DP-1".to_string(),
+                Source::Path(PathBuf::from("/tmp/b.png")),
+            ),
+        ];
+        let config = load_bg_config(&context).unwrap();
+        assert!(entries.iter().all(|e| needs_write(&config, e)));
+
+        Backend::CosmicBg.apply(&context, &entries);
+        let config = load_bg_config(&context).unwrap();
+        assert!(entries.iter().all(|e| !needs_write(&config, e)));
+        let changed = Entry::new(
+            "DP-1".to_string(),
+            Source::Path(PathBuf::from("/tmp/c.png")),
+        );
+        assert!(needs_write(&config, &changed));
+    }
+}
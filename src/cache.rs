@@ -0,0 +1,106 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Caches the colors extracted from the wallpapers, so they're only computed again when the
+//! image changes.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use cosmic::iced::Color;
+
+use crate::colors::Extraction;
+
+#[derive(Debug, Clone, Default)]
+pub struct ColorCache {
+    /// The colors keyed by the canonical path of the image, along with the modification time of
+    /// the image and the parameters they were extracted with.
+    entries: HashMap<PathBuf, (SystemTime, Extraction, Vec<Color>)>,
+    hits: usize,
+    misses: usize,
+}
+
+impl ColorCache {
+    /// Whether the colors of the image at the path are cached and current. An image that can't
+    /// be read has no colors to extract, so it counts as cached.
+    ///
+    /// Symlinks are resolved for the key, so retargeting one or modifying the image it points
+    /// to invalidates the cached colors.
+    pub fn has(&self, path: &Path, extraction: Extraction) -> bool {
+        let Some((key, modified)) = key(path) else {
+            return true;
+        };
+        matches!(self.entries.get(&key), Some((m, e, _)) if *m == modified && *e == extraction)
+    }
+
+    /// The cached colors of the image at the path if they're current, and none for an image
+    /// that can't be read.
+    pub fn get(&mut self, path: &Path, extraction: Extraction) -> Option<Vec<Color>> {
+        let Some((key, modified)) = key(path) else {
+            return Some(vec![]);
+        };
+        match self.entries.get(&key) {
+            Some((m, e, colors)) if *m == modified && *e == extraction => {
+                self.hits += 1;
+                Some(colors.clone())
+            }
+            _ => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Caches the colors extracted from the image at the path.
+    pub fn insert(&mut self, path: &Path, extraction: Extraction, colors: Vec<Color>) {
+        if let Some((key, modified)) = key(path) {
+            self.entries.insert(key, (modified, extraction, colors));
+        }
+    }
+}
+
+impl ColorCache {
+    /// The number of lookups that found colors in the cache and the number that had to extract
+    /// them, since the applet started.
+    pub fn stats(&self) -> (usize, usize) {
+        (self.hits, self.misses)
+    }
+}
+
+fn key(path: &Path) -> Option<(PathBuf, SystemTime)> {
+    let path = fs::canonicalize(path).ok()?;
+    let modified = fs::metadata(&path).ok()?.modified().ok()?;
+    Some((path, modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    #[test]
+    fn retargeted_symlink_invalidates() {
+        let dir = tempfile::tempdir().unwrap();
+        let [a, b, link] = ["a.png", "b.png", "link.png"].map(|name| dir.path().join(name));
+        fs::write(&a, b"a").unwrap();
+        fs::write(&b, b"b").unwrap();
+        symlink(&a, &link).unwrap();
+
+        let extraction = Extraction::default();
+        let colors = vec![Color::from_rgb(0.2, 0.4, 0.6)];
+        let mut cache = ColorCache::default();
+        cache.insert(&link, extraction, colors.clone());
+        assert!(cache.has(&link, extraction));
+        assert_eq!(cache.get(&link, extraction), Some(colors.clone()));
+        // The image itself shares the entry of the link pointing to it.
+        assert!(cache.has(&a, extraction));
+
+        fs::remove_file(&link).unwrap();
+        symlink(&b, &link).unwrap();
+        assert!(!cache.has(&link, extraction));
+        assert_eq!(cache.get(&link, extraction), None);
+        assert_eq!(cache.stats(), (1, 1));
+    }
+}
@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Subcommands exposing the applet's color extraction to scripts.
+
+use std::fs;
+use std::io::{self, Read};
+use std::process::ExitCode;
+
+use cosmic::iced::Color;
+use cosmic::iced_winit::graphics::image::image_rs;
+
+use crate::colors::{self, Extraction, Quality};
+
+/// The size images are scaled down to before extraction, roughly the size of
+/// the thumbnails wallpapers are extracted from.
+const THUMBNAIL_SIZE: u32 = 300;
+
+/// Runs the subcommand given on the command line, if there is one. Any other arguments are left
+/// to the applet.
+pub fn run(mut args: impl Iterator<Item = String>) -> Option<ExitCode> {
+    match args.next()?.as_str() {
+        "palette" => Some(palette(args.next())),
+        _ => None,
+    }
+}
+
+/// Prints the colors of the image at the path, or read from stdin if the path is `-` or
+/// missing, as hex codes, one per line.
+fn palette(path: Option<String>) -> ExitCode {
+    // Scripts expect the same image to always give the same output.
+    let extraction = Extraction::new(Quality::default(), true);
+    let colors = match path.as_deref() {
+        None | Some("-") => from_stdin(extraction),
+        Some(path) => fs::read(path)
+            .map_err(|why| format!("failed to read {path}: {why}"))
+            .and_then(|image| from_memory(&image, extraction)),
+    };
+    match colors {
+        Ok(colors) if !colors.is_empty() => {
+            println!("{}", colors::palette_to_hex(&colors));
+            ExitCode::SUCCESS
+        }
+        Ok(_) => {
+            eprintln!("no colors found");
+            ExitCode::FAILURE
+        }
+        Err(why) => {
+            eprintln!("{why}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn from_stdin(extraction: Extraction) -> Result<Vec<Color>, String> {
+    let mut image = Vec::new();
+    io::stdin()
+        .read_to_end(&mut image)
+        .map_err(|why| format!("failed to read stdin: {why}"))?;
+    from_memory(&image, extraction)
+}
+
+fn from_memory(image: &[u8], extraction: Extraction) -> Result<Vec<Color>, String> {
+    let image = image_rs::load_from_memory(image)
+        .map_err(|why| format!("failed to decode image: {why}"))?
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .to_rgb8();
+    Ok(colors::colors_from_rgb(image.as_raw(), extraction))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaves_other_arguments_to_the_applet() {
+        assert!(run(std::iter::empty()).is_none());
+        assert!(run(["--verbose".to_string()].into_iter()).is_none());
+    }
+
+    #[test]
+    fn unknown_path_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.png");
+        let code = run(["palette".to_string(), path.display().to_string()].into_iter());
+        assert_eq!(code, Some(ExitCode::FAILURE));
+    }
+}
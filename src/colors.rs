@@ -0,0 +1,811 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Helpers for working with the colors extracted from the wallpapers.
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::ops::RangeInclusive;
+
+use cosmic::cosmic_theme::palette::{Darken, FromColor, Hsl, Lab, Lighten, LinSrgb, Mix, Srgb};
+use cosmic::iced::{color, Color};
+use serde::{Deserialize, Serialize};
+
+use crate::unique::UniqueIterator;
+
+/// The hue of the color in degrees, in the `0.0..360.0` range.
+pub fn hue(color: Color) -> f32 {
+    Hsl::from_color(Srgb::from(color))
+        .hue
+        .into_positive_degrees()
+}
+
+/// The color with its HSL saturation and lightness shifted by the given amounts, each in the
+/// `-1.0..=1.0` range. The hue is left as is.
+pub fn adjust(color: Color, saturation: f32, lightness: f32) -> Color {
+    let mut hsl = Hsl::from_color(Srgb::from(color));
+    hsl.saturation = (hsl.saturation + saturation).clamp(0.0, 1.0);
+    hsl.lightness = (hsl.lightness + lightness).clamp(0.0, 1.0);
+    Srgb::from_color(hsl).into()
+}
+
+/// The given number of shades of the color, evenly spaced in lightness from lightest to
+/// darkest, half of them lighter and half darker than the color. An odd one out is lighter.
+pub fn shades(color: Color, count: u8) -> Vec<Color> {
+    let color = Srgb::from(color);
+    let lighter = count.div_ceil(2);
+    let darker = count / 2;
+    let lighter = (1..=lighter)
+        .rev()
+        .map(|i| color.lighten(i as f32 / (lighter + 1) as f32).into());
+    let darker = (1..=darker).map(|i| color.darken(i as f32 / (darker + 1) as f32).into());
+    lighter.chain(darker).collect()
+}
+
+/// A color vision deficiency the swatches can be previewed under.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorVision {
+    /// No working red cones.
+    Protanopia,
+    /// No working green cones.
+    Deuteranopia,
+    /// No working blue cones.
+    Tritanopia,
+}
+
+impl ColorVision {
+    /// The transform from linear RGB to how it is seen, from Machado et al., "A Physiologically-
+    /// based Model for Simulation of Color Vision Deficiency", at full severity.
+    fn matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            ColorVision::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            ColorVision::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            ColorVision::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+}
+
+/// How the color looks to someone with the color vision deficiency.
+pub fn simulate(color: Color, vision: ColorVision) -> Color {
+    let linear = Srgb::from(color).into_linear();
+    let rgb = [linear.red, linear.green, linear.blue];
+    let [r, g, b] = vision.matrix().map(|row| {
+        row.iter()
+            .zip(rgb)
+            .map(|(m, c)| m * c)
+            .sum::<f32>()
+            .clamp(0.0, 1.0)
+    });
+    Srgb::from_linear(LinSrgb::new(r, g, b)).into()
+}
+
+/// Mixes the color into the accent, keeping `factor` of the accent, so 0 yields the color
+/// itself and 1 leaves the accent unchanged.
+pub fn blend(color: Color, accent: Color, factor: f32) -> Color {
+    Srgb::from(color)
+        .mix(Srgb::from(accent), factor.clamp(0.0, 1.0))
+        .into()
+}
+
+/// A preference for warm or cool colors when picking an accent automatically.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Temperature {
+    /// Reds, oranges and yellows.
+    Warm,
+    /// Greens, cyans and blues.
+    Cool,
+}
+
+impl Temperature {
+    fn contains(self, hue: f32) -> bool {
+        match self {
+            Temperature::Warm => !(90.0..330.0).contains(&hue),
+            Temperature::Cool => (120.0..270.0).contains(&hue),
+        }
+    }
+}
+
+/// How much a color matching the temperature bias is preferred over one that doesn't.
+const TEMPERATURE_WEIGHT: f32 = 2.0;
+
+/// Picks the most vivid color of the palette as the accent. Saturated colors of medium
+/// lightness score best, and colors of the preferred temperature, if any, score higher still.
+pub fn select_accent(colors: &[Color], bias: Option<Temperature>) -> Option<Color> {
+    let score = |color: Color| {
+        let hsl = Hsl::from_color(Srgb::from(color));
+        let vividness = hsl.saturation * (1.0 - (2.0 * hsl.lightness - 1.0).abs());
+        match bias {
+            Some(bias) if bias.contains(hsl.hue.into_positive_degrees()) => {
+                vividness * TEMPERATURE_WEIGHT
+            }
+            _ => vividness,
+        }
+    };
+    colors
+        .iter()
+        .copied()
+        .max_by(|&l, &r| score(l).total_cmp(&score(r)))
+}
+
+/// Colors less saturated than this are grays whose hue means nothing.
+const MIN_HUE_SATURATION: f32 = 0.1;
+
+/// Picks the color of the palette whose hue is closest to the given one, in degrees. Grays are
+/// only considered if the palette has nothing else.
+pub fn nearest_hue(colors: &[Color], hue: f32) -> Option<Color> {
+    let hue = hue.rem_euclid(360.0);
+    let distance = |color: Color| {
+        let hsl = Hsl::from_color(Srgb::from(color));
+        let diff = (hsl.hue.into_positive_degrees() - hue).abs();
+        let diff = diff.min(360.0 - diff);
+        if hsl.saturation < MIN_HUE_SATURATION {
+            diff + 360.0
+        } else {
+            diff
+        }
+    };
+    colors
+        .iter()
+        .copied()
+        .min_by(|&l, &r| distance(l).total_cmp(&distance(r)))
+}
+
+/// The order swatches are shown in: by hue starting from red, then from light to dark for the
+/// same hue, followed by the grays from light to dark. It only depends on the colors, so the same
+/// palette is always shown the same way however it was extracted.
+pub fn palette_order(a: Color, b: Color) -> Ordering {
+    let key = |color: Color| {
+        let hsl = Hsl::from_color(Srgb::from(color));
+        let gray = hsl.saturation < MIN_HUE_SATURATION;
+        let hue = if gray {
+            0.0
+        } else {
+            hsl.hue.into_positive_degrees()
+        };
+        (gray, hue, -hsl.lightness)
+    };
+    let ((a_gray, a_hue, a_dark), (b_gray, b_hue, b_dark)) = (key(a), key(b));
+    a_gray
+        .cmp(&b_gray)
+        .then(a_hue.total_cmp(&b_hue))
+        .then(a_dark.total_cmp(&b_dark))
+}
+
+/// Picks a random color of the palette, avoiding the current one unless it's the only choice.
+pub fn pick_random(
+    colors: &[Color],
+    current: Option<Color>,
+    rng: &mut fastrand::Rng,
+) -> Option<Color> {
+    let others = colors
+        .iter()
+        .copied()
+        .filter(|&c| Some(c) != current)
+        .collect::<Vec<_>>();
+    if others.is_empty() {
+        colors.first().copied()
+    } else {
+        Some(others[rng.usize(..others.len())])
+    }
+}
+
+/// The contrast ratio WCAG requires for normal text.
+pub const MIN_CONTRAST: f32 = 4.5;
+
+/// The relative luminance of the color as defined by WCAG.
+pub fn relative_luminance(color: Color) -> f32 {
+    let linear = |c: f32| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(color.r) + 0.7152 * linear(color.g) + 0.0722 * linear(color.b)
+}
+
+/// The WCAG contrast ratio between the two colors, from 1.0 to 21.0.
+pub fn contrast_ratio(a: Color, b: Color) -> f32 {
+    let (a, b) = (relative_luminance(a), relative_luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Black or white, whichever is easier to read on the background.
+pub fn readable_text_color(background: Color) -> Color {
+    if contrast_ratio(Color::BLACK, background) > contrast_ratio(Color::WHITE, background) {
+        Color::BLACK
+    } else {
+        Color::WHITE
+    }
+}
+
+/// The colors with a relative luminance within the bounds, which leaves out the near-black and
+/// near-white ones no accent looks good in. If that leaves none, the most saturated color is kept
+/// so there is always something to pick.
+pub fn within_luminance(colors: Vec<Color>, min: f32, max: f32) -> Vec<Color> {
+    let saturation = |color: Color| Hsl::from_color(Srgb::from(color)).saturation;
+    let most_saturated = colors
+        .iter()
+        .copied()
+        .max_by(|&l, &r| saturation(l).total_cmp(&saturation(r)));
+    let within = colors
+        .into_iter()
+        .filter(|&color| (min..=max).contains(&relative_luminance(color)))
+        .collect::<Vec<_>>();
+    if within.is_empty() {
+        most_saturated.into_iter().collect()
+    } else {
+        within
+    }
+}
+
+/// The mean of the colors, each weighted by how often it occurs.
+pub fn weighted_average(colors: &[(Color, usize)]) -> Option<Color> {
+    let total = colors.iter().map(|&(_, count)| count).sum::<usize>();
+    if total == 0 {
+        return None;
+    }
+    let (r, g, b) = colors
+        .iter()
+        .fold((0.0, 0.0, 0.0), |(r, g, b), &(color, count)| {
+            let count = count as f32;
+            (
+                r + color.r * count,
+                g + color.g * count,
+                b + color.b * count,
+            )
+        });
+    let total = total as f32;
+    Some(Color::from_rgb(r / total, g / total, b / total))
+}
+
+/// The contrast an automatically picked accent should at least have with the overall color of
+/// the wallpapers, so it doesn't blend into them.
+pub const MIN_WALLPAPER_CONTRAST: f32 = 1.5;
+
+/// The color closest in lightness to the given one that has at least the given contrast ratio
+/// against the background. Falls back to black or white, whichever contrasts more.
+pub fn ensure_contrast(color: Color, background: Color, ratio: f32) -> Color {
+    if contrast_ratio(color, background) >= ratio {
+        return color;
+    }
+    let hsl = Hsl::from_color(Srgb::from(color));
+    (1..=100)
+        .map(|step| step as f32 / 100.0)
+        .flat_map(|delta| [hsl.lightness - delta, hsl.lightness + delta])
+        .filter(|lightness| (0.0..=1.0).contains(lightness))
+        .map(|lightness| Color::from(Srgb::from_color(Hsl { lightness, ..hsl })))
+        .find(|&candidate| contrast_ratio(candidate, background) >= ratio)
+        .unwrap_or_else(|| readable_text_color(background))
+}
+
+/// The order the swatches are shown in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SwatchSort {
+    /// The colors covering most of the wallpapers first.
+    Frequency,
+    /// From light to dark.
+    Luminance,
+    /// By hue, as `palette_order` sorts them.
+    #[default]
+    Hue,
+}
+
+/// How the distance between two colors is measured.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ColorMetric {
+    /// Euclidean distance between the sRGB components.
+    Rgb,
+    /// Euclidean distance in the CIELAB color space (CIE76), which follows perception closely.
+    #[default]
+    CieLab,
+}
+
+impl ColorMetric {
+    /// The distance under which two colors are indistinguishable.
+    pub fn just_noticeable(&self) -> f32 {
+        match self {
+            ColorMetric::Rgb => 0.02,
+            ColorMetric::CieLab => 2.3,
+        }
+    }
+}
+
+/// The distance between two colors measured with the given metric.
+pub fn color_distance(a: Color, b: Color, metric: ColorMetric) -> f32 {
+    let (a, b) = match metric {
+        ColorMetric::Rgb => ([a.r, a.g, a.b], [b.r, b.g, b.b]),
+        ColorMetric::CieLab => {
+            let a = Lab::from_color(Srgb::from(a));
+            let b = Lab::from_color(Srgb::from(b));
+            ([a.l, a.a, a.b], [b.l, b.a, b.b])
+        }
+    };
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<f32>()
+        .sqrt()
+}
+
+/// Whether the two colors are closer than `distance` times the just noticeable difference of the
+/// metric, so a distance of 1.0 means they look the same.
+pub fn similar(a: Color, b: Color, metric: ColorMetric, distance: f32) -> bool {
+    color_distance(a, b, metric) < metric.just_noticeable() * distance
+}
+
+/// The color components rounded to their `bits` most significant bits, so that colors
+/// differing only by rounding errors compare equal.
+pub fn quantize(color: Color, bits: u8) -> [u16; 3] {
+    let shift = 8 - bits.clamp(1, 8);
+    let half = (1 << shift) >> 1;
+    let [r, g, b, _] = color
+        .into_rgba8()
+        .map(|component| (u16::from(component) + half) >> shift);
+    [r, g, b]
+}
+
+/// The swatches without the ones equal to an earlier one once quantized to `bits`, or without
+/// exact duplicates if unset.
+pub fn unique_swatches(swatches: Vec<Color>, bits: Option<u8>) -> Vec<Color> {
+    match bits {
+        Some(bits) => swatches
+            .into_iter()
+            .collect_unique_by_key(|&color| quantize(color, bits)),
+        None => swatches.into_iter().collect_unique(),
+    }
+}
+
+/// The color as a `#rrggbb` hex code.
+pub fn to_hex(color: Color) -> String {
+    let [r, g, b, _] = color.into_rgba8();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// The colors as hex codes, one per line.
+pub fn palette_to_hex(colors: &[Color]) -> String {
+    colors
+        .iter()
+        .map(|&color| to_hex(color))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parses a six digit hex code, with or without a leading `#` or `0x`.
+pub fn parse_hex(hex: &str) -> Option<Color> {
+    let hex = hex
+        .strip_prefix('#')
+        .or_else(|| hex.strip_prefix("0x"))
+        .unwrap_or(hex);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+    Some(Color::from_rgb8(
+        (rgb >> 16) as u8,
+        (rgb >> 8) as u8,
+        rgb as u8,
+    ))
+}
+
+/// Parses the colors of a color-scheme file, like a base16 scheme (`base0D: "7cafc2"`),
+/// Xresources (`*.color4: #7cafc2`), a kitty theme (`color4 #7cafc2`) or a plain list of hex
+/// codes as written by `palette_to_hex`. Lines without a color, comments and blank lines are
+/// skipped.
+pub fn parse_scheme(scheme: &str) -> Vec<Color> {
+    scheme
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(['!', ';']) && !line.starts_with("//"))
+        .filter_map(|line| {
+            let mut tokens = line
+                .split(|c: char| c.is_whitespace() || matches!(c, ':' | '=' | ',' | '"' | '\''))
+                .filter(|token| !token.is_empty());
+            let first = tokens.next()?;
+            match parse_hex(first) {
+                Some(color) => Some(color),
+                // Anything else starting with a `#` is a comment, so is the rest of a line after one.
+                None if first.starts_with('#') => None,
+                None => tokens
+                    .take_while(|token| !token.starts_with('#') || parse_hex(token).is_some())
+                    .find_map(parse_hex),
+            }
+        })
+        .collect()
+}
+
+/// A trade-off between the speed and the accuracy of the color extraction.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Quality {
+    /// Samples a quarter of the thumbnail's pixels and only keeps strongly represented colors.
+    Low,
+    /// Samples every pixel of the thumbnail.
+    #[default]
+    Medium,
+    /// Samples every pixel of the thumbnail and also keeps faintly represented colors.
+    High,
+}
+
+impl Quality {
+    /// The share of the thumbnail's pixels fed to the quantizer.
+    fn sample_fraction(self) -> f32 {
+        match self {
+            Quality::Low => 0.25,
+            Quality::Medium | Quality::High => 1.0,
+        }
+    }
+
+    /// The minimum share of the pixels a color has to cover to be kept.
+    pub fn threshold(self) -> f32 {
+        match self {
+            Quality::Low => 0.01,
+            Quality::Medium => 0.001,
+            Quality::High => 0.0005,
+        }
+    }
+}
+
+/// The parameters of the dominant color extraction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Extraction {
+    /// Sample the pixels at a fixed stride instead of leaving it to the quantizer, and sort the
+    /// extracted colors by their components, so the same image always yields the same palette in
+    /// the same order regardless of how the quantizer samples and orders its output.
+    pub deterministic: bool,
+    pub sample_fraction: f32,
+    pub threshold: f32,
+}
+
+impl Extraction {
+    pub fn new(quality: Quality, deterministic: bool) -> Self {
+        Self {
+            deterministic,
+            sample_fraction: quality.sample_fraction(),
+            threshold: quality.threshold(),
+        }
+    }
+}
+
+/// The thresholds that can be configured in place of the one of the quality.
+pub const THRESHOLD_RANGE: RangeInclusive<f32> = 0.0001..=0.05;
+
+impl Extraction {
+    /// The extraction with the given threshold, clamped to `THRESHOLD_RANGE`. A NaN or negative
+    /// threshold keeps the one of the quality.
+    pub fn with_threshold(self, threshold: f32) -> Self {
+        if threshold.is_nan() || threshold < 0.0 {
+            tracing::warn!(threshold, "invalid color threshold, using the default");
+            return self;
+        }
+        Self {
+            threshold: threshold.clamp(*THRESHOLD_RANGE.start(), *THRESHOLD_RANGE.end()),
+            ..self
+        }
+    }
+}
+
+impl Default for Extraction {
+    fn default() -> Self {
+        Self::new(Quality::default(), false)
+    }
+}
+
+/// Parses packed RGB triplets. A trailing partial triplet can't be a color and is dropped, but
+/// since that means the buffer is malformed it is logged.
+pub fn parse_rgb(buffer: &[u8]) -> Vec<Color> {
+    let chunks = buffer.chunks_exact(3);
+    if !chunks.remainder().is_empty() {
+        tracing::warn!(
+            len = buffer.len(),
+            "color buffer length is not a multiple of 3, ignoring the trailing bytes"
+        );
+    }
+    chunks.map(|s| color!(s[0], s[1], s[2])).collect()
+}
+
+/// The dominant colors of an image given as packed RGB pixels.
+pub fn colors_from_rgb(pixels: &[u8], extraction: Extraction) -> Vec<Color> {
+    let count = pixels.len() / 3;
+    if count == 0 {
+        return vec![];
+    }
+    let pixels = &pixels[..count * 3];
+    let (pixels, samples) = if extraction.deterministic {
+        // The quantizer is handed every pixel it gets, the ones sampled here.
+        let stride = (1.0 / extraction.sample_fraction.max(f32::EPSILON)).round() as usize;
+        let sampled = pixels
+            .chunks_exact(3)
+            .step_by(stride.max(1))
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        let samples = sampled.len() as u32 / 3;
+        (Cow::Owned(sampled), samples)
+    } else {
+        let samples = (count as f32 * extraction.sample_fraction) as u32;
+        (Cow::Borrowed(pixels), samples)
+    };
+    let mut colors = parse_rgb(&dominant_color::get_colors_with_config(
+        &pixels,
+        false,
+        samples.max(1).into(),
+        extraction.threshold,
+    ));
+    if extraction.deterministic {
+        colors.sort_by_key(|color| color.into_rgba8());
+    }
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_QUANTIZE_BITS;
+
+    #[test]
+    fn unique_swatches_at_two_tolerances() {
+        let swatches = vec![
+            Color::from_rgb8(0x80, 0x80, 0x80),
+            Color::from_rgb8(0x83, 0x83, 0x83),
+            Color::from_rgb8(0x20, 0x80, 0xe0),
+        ];
+        assert_eq!(unique_swatches(swatches.clone(), Some(8)), swatches);
+        assert_eq!(
+            unique_swatches(swatches.clone(), Some(DEFAULT_QUANTIZE_BITS)),
+            [swatches[0], swatches[2]]
+        );
+    }
+
+    #[test]
+    fn readable_text_color_flips_at_equal_contrast() {
+        // Black and white contrast equally at a relative luminance of sqrt(1.05 * 0.05) - 0.05.
+        let boundary = (1.05f32 * 0.05).sqrt() - 0.05;
+        let gray = |v: f32| Color::from_rgb(v, v, v);
+        let (darker, lighter) = (gray(0.45), gray(0.47));
+        assert!(relative_luminance(darker) < boundary);
+        assert!(relative_luminance(lighter) > boundary);
+        assert_eq!(readable_text_color(darker), Color::WHITE);
+        assert_eq!(readable_text_color(lighter), Color::BLACK);
+        assert_eq!(readable_text_color(Color::BLACK), Color::WHITE);
+        assert_eq!(readable_text_color(Color::WHITE), Color::BLACK);
+    }
+
+    /// A 32x32 image of four flat quadrants with a little noise, as packed RGB pixels.
+    fn quadrants() -> Vec<u8> {
+        let quadrant = [[200, 40, 40], [40, 160, 60], [30, 60, 200], [230, 220, 200]];
+        (0..32 * 32)
+            .flat_map(|i: usize| {
+                let (x, y) = (i % 32, i / 32);
+                let [r, g, b] = quadrant[usize::from(x >= 16) + 2 * usize::from(y >= 16)];
+                let noise = (i * 7 % 5) as u8;
+                [r + noise, g + noise, b + noise]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deterministic_extraction_repeats() {
+        let pixels = quadrants();
+        for quality in [Quality::Low, Quality::Medium, Quality::High] {
+            let extraction = Extraction::new(quality, true);
+            let first = colors_from_rgb(&pixels, extraction);
+            assert!(!first.is_empty());
+            assert_eq!(colors_from_rgb(&pixels, extraction), first);
+        }
+    }
+
+    #[test]
+    fn color_distance_under_both_metrics() {
+        let red = Color::from_rgb(1.0, 0.0, 0.0);
+        for metric in [ColorMetric::Rgb, ColorMetric::CieLab] {
+            assert_eq!(color_distance(red, red, metric), 0.0);
+            assert_eq!(
+                color_distance(red, Color::WHITE, metric),
+                color_distance(Color::WHITE, red, metric)
+            );
+        }
+        let rgb = color_distance(Color::BLACK, Color::WHITE, ColorMetric::Rgb);
+        assert!((rgb - 3f32.sqrt()).abs() < 1e-6);
+        // Black and white only differ in lightness, which CIELAB puts 100 apart.
+        let lab = color_distance(Color::BLACK, Color::WHITE, ColorMetric::CieLab);
+        assert!((lab - 100.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn colors_differing_in_the_sixth_decimal_dedup_to_one() {
+        let swatches = vec![
+            Color::from_rgb(0.5, 0.25, 0.75),
+            Color::from_rgb(0.500001, 0.25, 0.75),
+        ];
+        assert_eq!(unique_swatches(swatches.clone(), None).len(), 2);
+        assert_eq!(
+            unique_swatches(swatches.clone(), Some(DEFAULT_QUANTIZE_BITS)),
+            [swatches[0]]
+        );
+    }
+
+    fn assert_close(a: Color, b: Color) {
+        let close = [(a.r, b.r), (a.g, b.g), (a.b, b.b)]
+            .iter()
+            .all(|(a, b)| (a - b).abs() < 1e-3);
+        assert!(close, "{a:?} is not {b:?}");
+    }
+
+    #[test]
+    fn adjust_shifts_saturation_and_lightness() {
+        let red = Color::from_rgb(1.0, 0.0, 0.0);
+        assert_close(adjust(red, 0.0, 0.0), red);
+        assert_close(adjust(red, 0.0, 0.25), Color::from_rgb(1.0, 0.5, 0.5));
+        assert_close(adjust(red, -0.5, 0.0), Color::from_rgb(0.75, 0.25, 0.25));
+        assert_close(adjust(red, -1.0, 0.0), Color::from_rgb(0.5, 0.5, 0.5));
+        // Shifts past the ends of the range are clamped.
+        assert_close(adjust(red, 0.0, 1.0), Color::WHITE);
+        assert_close(adjust(red, 0.0, -1.0), Color::BLACK);
+    }
+
+    #[test]
+    fn temperature_bias_changes_the_accent() {
+        // Both are of medium lightness, the blue is a bit more saturated.
+        let orange = Color::from_rgb(0.9, 0.5, 0.1);
+        let blue = Color::from_rgb(0.05, 0.4, 0.95);
+        let palette = [orange, blue, Color::from_rgb(0.5, 0.5, 0.5)];
+        assert_eq!(select_accent(&palette, None), Some(blue));
+        assert_eq!(
+            select_accent(&palette, Some(Temperature::Warm)),
+            Some(orange)
+        );
+        assert_eq!(select_accent(&palette, Some(Temperature::Cool)), Some(blue));
+        assert_eq!(select_accent(&[], Some(Temperature::Warm)), None);
+    }
+
+    #[test]
+    fn nearest_hue_in_a_palette() {
+        let red = Color::from_rgb(1.0, 0.0, 0.0);
+        let green = Color::from_rgb(0.0, 1.0, 0.0);
+        let blue = Color::from_rgb(0.0, 0.0, 1.0);
+        let gray = Color::from_rgb(0.5, 0.5, 0.5);
+        let palette = [gray, red, green, blue];
+        assert_eq!(nearest_hue(&palette, 100.0), Some(green));
+        assert_eq!(nearest_hue(&palette, 200.0), Some(blue));
+        // Hues wrap around at 360 degrees.
+        assert_eq!(nearest_hue(&palette, 350.0), Some(red));
+        assert_eq!(nearest_hue(&palette, -10.0), Some(red));
+        // Grays are only picked without anything else.
+        assert_eq!(nearest_hue(&[gray], 100.0), Some(gray));
+        assert_eq!(nearest_hue(&[], 100.0), None);
+    }
+
+    #[test]
+    fn parse_rgb_drops_a_trailing_partial_color() {
+        let buffer = [255, 0, 0, 0, 255, 0, 7];
+        assert_eq!(
+            parse_rgb(&buffer),
+            [Color::from_rgb8(255, 0, 0), Color::from_rgb8(0, 255, 0)]
+        );
+        assert!(parse_rgb(&[1, 2]).is_empty());
+    }
+
+    #[test]
+    fn ensure_contrast_adjusts_lightness_until_readable() {
+        // Black already contrasts enough with white.
+        assert_eq!(
+            ensure_contrast(Color::BLACK, Color::WHITE, MIN_CONTRAST),
+            Color::BLACK
+        );
+        let yellow = Color::from_rgb(1.0, 1.0, 0.0);
+        let adjusted = ensure_contrast(yellow, Color::WHITE, MIN_CONTRAST);
+        assert!(contrast_ratio(adjusted, Color::WHITE) >= MIN_CONTRAST);
+        assert!(relative_luminance(adjusted) < relative_luminance(yellow));
+        assert!((hue(adjusted) - hue(yellow)).abs() < 1.0);
+        // No color reaches more than 21:1.
+        assert_eq!(ensure_contrast(yellow, Color::WHITE, 22.0), Color::BLACK);
+    }
+
+    #[test]
+    fn blend_keeps_the_share_of_the_accent() {
+        let accent = Color::from_rgb(0.2, 0.4, 0.8);
+        assert_close(blend(Color::WHITE, accent, 0.0), Color::WHITE);
+        assert_close(blend(Color::WHITE, accent, 1.0), accent);
+        assert_close(
+            blend(Color::BLACK, Color::WHITE, 0.25),
+            Color::from_rgb(0.25, 0.25, 0.25),
+        );
+        // Factors out of range are clamped.
+        assert_close(blend(Color::WHITE, accent, 2.0), accent);
+    }
+
+    #[test]
+    fn weighted_average_of_a_known_palette() {
+        let palette = [
+            (Color::from_rgb(1.0, 0.0, 0.0), 3),
+            (Color::from_rgb(0.0, 0.0, 1.0), 1),
+        ];
+        assert_close(
+            weighted_average(&palette).unwrap(),
+            Color::from_rgb(0.75, 0.0, 0.25),
+        );
+        assert_eq!(weighted_average(&[]), None);
+        assert_eq!(weighted_average(&[(Color::WHITE, 0)]), None);
+    }
+
+    /// Checks that the vision keeps black and white, which every row of its matrix sums to one
+    /// for, and sees the primary as the column of the matrix.
+    fn assert_simulates(vision: ColorVision, primary: usize) {
+        assert_close(simulate(Color::BLACK, vision), Color::BLACK);
+        assert_close(simulate(Color::WHITE, vision), Color::WHITE);
+        let mut linear = [0.0; 3];
+        linear[primary] = 1.0;
+        let [r, g, b] = linear;
+        let seen = simulate(Srgb::from_linear(LinSrgb::new(r, g, b)).into(), vision);
+        let [r, g, b] = vision.matrix().map(|row| row[primary].clamp(0.0, 1.0));
+        assert_close(seen, Srgb::from_linear(LinSrgb::new(r, g, b)).into());
+    }
+
+    #[test]
+    fn simulate_protanopia() {
+        assert_simulates(ColorVision::Protanopia, 0);
+    }
+
+    #[test]
+    fn simulate_deuteranopia() {
+        assert_simulates(ColorVision::Deuteranopia, 1);
+    }
+
+    #[test]
+    fn simulate_tritanopia() {
+        assert_simulates(ColorVision::Tritanopia, 2);
+    }
+
+    #[test]
+    fn palette_order_is_the_same_for_two_extractions() {
+        let pixels = quadrants();
+        let extraction = Extraction::new(Quality::Medium, false);
+        let order = || {
+            let mut colors = colors_from_rgb(&pixels, extraction);
+            colors.sort_by(|&l, &r| palette_order(l, r));
+            colors
+        };
+        let first = order();
+        assert!(!first.is_empty());
+        assert_eq!(order(), first);
+        // Sorting doesn't depend on the order the colors came in.
+        let mut reversed = first.iter().rev().copied().collect::<Vec<_>>();
+        reversed.sort_by(|&l, &r| palette_order(l, r));
+        assert_eq!(reversed, first);
+    }
+
+    #[test]
+    fn pick_random_is_reproducible_and_avoids_the_current() {
+        let palette = [
+            Color::from_rgb(1.0, 0.0, 0.0),
+            Color::from_rgb(0.0, 1.0, 0.0),
+            Color::from_rgb(0.0, 0.0, 1.0),
+        ];
+        let current = Some(palette[1]);
+        let picks = |seed| {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            (0..32)
+                .map(|_| pick_random(&palette, current, &mut rng).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let first = picks(7);
+        assert_eq!(picks(7), first);
+        assert!(first.iter().all(|&color| Some(color) != current));
+        assert!(first.contains(&palette[0]) && first.contains(&palette[2]));
+
+        let mut rng = fastrand::Rng::with_seed(7);
+        assert_eq!(pick_random(&palette[1..2], current, &mut rng), current);
+        assert_eq!(pick_random(&[], current, &mut rng), None);
+    }
+}
@@ -1,48 +1,739 @@
 // SPDX-License-Identifier: GPL-3.0
 
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime};
 use cosmic::{
-    cosmic_config::{self, cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry},
+    cosmic_config::{self, cosmic_config_derive::CosmicConfigEntry, ConfigGet, CosmicConfigEntry},
+    cosmic_theme::palette::Srgb,
+    widget::icon,
     Application,
 };
-use cosmic_bg_config::{context, Entry};
+use cosmic_bg_config::{context, Context, Entry, ScalingMode, Source};
+use serde::{Deserialize, Serialize};
 
 use crate::app::AppModel;
+use crate::backend::{Backend, WallpaperScope};
+use crate::colors::{ColorMetric, Extraction, Quality, SwatchSort, Temperature};
+use crate::solar;
 
-#[derive(Default, Debug, Clone, CosmicConfigEntry, PartialEq)]
+#[derive(Debug, Clone, CosmicConfigEntry, PartialEq)]
 #[version = 1]
 pub struct Config {
-    pub enabled: bool,
+    /// Whether the dark mode wallpapers are applied when switching to dark mode.
+    pub dark_enabled: bool,
+    /// Whether the light mode wallpapers are applied when switching to light mode.
+    pub light_enabled: bool,
     pub dark: Vec<Entry>,
     pub light: Vec<Entry>,
+    /// Whether changing the fit of a mode's wallpapers also changes it for the same wallpapers
+    /// in the other mode.
+    pub link_modes: bool,
+    /// Whether wallpapers changed by something other than the user are changed back.
+    pub enforce_wallpapers: bool,
+    /// Whether colors are extracted from the wallpapers at all, which is the costliest part.
+    pub extract_colors: bool,
+    /// Use the color cosmic-bg paints for color sources instead of generating shades of it.
+    /// cosmic-bg keeps no color for image sources, so those are still run through the
+    /// extraction either way.
+    pub use_bg_color: bool,
+    /// How many swatches a wallpaper contributes at most, which is also how many the popup shows
+    /// before collapsing the rest.
+    pub color_count: u8,
+    /// How many shades a single color wallpaper contributes, up to `color_count`.
+    pub shade_count: u8,
+    /// Whether a single color wallpaper contributes its own color along with its shades.
+    pub include_base_color: bool,
+    /// Whether gradient wallpapers contribute swatches.
+    pub include_gradient_colors: bool,
+    /// Whether gradient wallpapers also contribute the mix of each pair of adjacent stops.
+    pub include_gradient_average: bool,
+    /// How close two swatches have to be to be merged into one.
+    pub color_metric: ColorMetric,
+    /// How many just noticeable differences of the metric apart swatches can be and still be
+    /// merged. The first swatch of the ones merged is the one kept.
+    pub color_merge_distance: f32,
+    /// The order the swatches are shown in.
+    pub swatch_sort: SwatchSort,
+    /// Swatches with a lower relative luminance are too close to black to be offered.
+    pub min_swatch_luminance: f32,
+    /// Swatches with a higher relative luminance are too close to white to be offered.
+    pub max_swatch_luminance: f32,
+    /// If set, swatches are compared using only this many bits per component. Unset by default,
+    /// so that only identical swatches are merged.
+    pub color_quantize_bits: Option<u8>,
+    /// Always extract the same palette in the same order from the same wallpaper.
+    pub deterministic_extraction: bool,
+    /// Whether the time color extraction takes is logged, at the debug level.
+    pub log_timings: bool,
+    /// How much effort goes into extracting the colors of image wallpapers.
+    pub quality: Quality,
+    /// If set, the minimum share of the pixels a color has to cover, in place of the one of
+    /// the quality.
+    pub color_threshold: Option<f32>,
+    /// Display names of the outputs the entries were captured on, keyed by output.
+    pub output_names: BTreeMap<String, String>,
+    /// If set, only the wallpaper of this output is extracted for the swatches, rather than the
+    /// ones of all outputs.
+    pub accent_source_output: Option<String>,
+    /// The palettes accents were most recently applied from, newest first.
+    pub history: Vec<PaletteRecord>,
+    /// Whether the accent last picked for a wallpaper is applied again along with it.
+    pub remember_accent: bool,
+    /// The accents last picked for the image wallpapers, keyed by path.
+    pub wallpaper_accents: BTreeMap<PathBuf, Srgb>,
+    /// Whether the applet changes the accent color at all, or only manages wallpapers.
+    pub manage_theme: bool,
+    /// Adjusts the lightness of the accent until it contrasts enough with the background.
+    pub high_contrast_accent: bool,
+    /// Whether the accent is also applied to GTK applications through the GNOME settings.
+    pub gtk_accent: bool,
+    /// Whether accent changes fade from the old accent to the new one, which takes several
+    /// writes of the theme.
+    pub animate_accent: bool,
+    /// Whether applying an accent also tints the windows with it.
+    pub window_hint_enabled: bool,
+    /// Whether the window hint can be picked separately instead of following the accent.
+    pub separate_window_hint: bool,
+    /// Whether picked swatches set the window hint rather than the accent, while it's picked
+    /// separately.
+    pub pick_window_hint: bool,
+    /// Whether an accent is picked from the palette automatically unless one was picked by hand.
+    pub auto_accent: bool,
+    /// The colors automatic accent selection prefers.
+    pub accent_temperature_bias: Option<Temperature>,
+    /// If set, automatic selection picks the color closest to this hue in degrees, so the
+    /// accent stays in the same hue family across wallpapers.
+    pub pinned_hue: Option<f32>,
+    /// How the wallpapers of a mode are applied.
+    pub backend: Backend,
+    /// Which of the entries of a mode are applied.
+    pub wallpaper_scope: WallpaperScope,
+    /// How long the theme mode has to stay the same before its wallpapers are applied, so
+    /// quickly toggling it doesn't make them flicker.
+    pub mode_switch_delay_ms: u64,
+    /// The name of the icon shown in the panel.
+    pub icon_name: String,
+    /// If set, the shown wallpapers are checked this often, since a slideshow changes them
+    /// without changing the config.
+    pub slideshow_poll_secs: Option<u64>,
+    /// If set, paused automation resumes by itself after this many hours.
+    pub pause_resume_hours: Option<u32>,
+    /// Whether the applet switches between dark and light mode at the scheduled times.
+    pub schedule_enabled: bool,
+    /// Whether the schedule follows the set times or the sun.
+    pub schedule_mode: ScheduleMode,
+    /// When the schedule switches to dark mode, unless it follows the sun.
+    pub dark_start: Time,
+    /// When the schedule switches to light mode, unless it follows the sun.
+    pub light_start: Time,
+    /// The latitude the sun is followed at, in degrees north.
+    pub latitude: f32,
+    /// The longitude the sun is followed at, in degrees east.
+    pub longitude: f32,
+    /// The command the dark mode settings button launches instead of the wallpaper settings.
+    pub dark_settings_command: Option<String>,
+    /// The command the light mode settings button launches instead of the wallpaper settings.
+    pub light_settings_command: Option<String>,
+}
+
+/// The bits per component swatches are compared on once turned on. Rounding away the last few
+/// bits merges swatches too close to tell apart without merging visibly different ones.
+pub const DEFAULT_QUANTIZE_BITS: u8 = 5;
+
+pub const DEFAULT_ICON: &str = "com.github.pstroka.BackgroundManager-symbolic";
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            dark_enabled: false,
+            light_enabled: false,
+            dark: Vec::new(),
+            light: Vec::new(),
+            link_modes: false,
+            enforce_wallpapers: false,
+            extract_colors: true,
+            use_bg_color: false,
+            color_count: 6,
+            shade_count: 4,
+            include_base_color: false,
+            include_gradient_colors: true,
+            include_gradient_average: true,
+            color_metric: ColorMetric::default(),
+            color_merge_distance: 1.0,
+            swatch_sort: SwatchSort::default(),
+            min_swatch_luminance: 0.01,
+            max_swatch_luminance: 0.9,
+            color_quantize_bits: None,
+            deterministic_extraction: false,
+            log_timings: false,
+            quality: Quality::default(),
+            color_threshold: None,
+            output_names: BTreeMap::new(),
+            accent_source_output: None,
+            history: Vec::new(),
+            remember_accent: false,
+            wallpaper_accents: BTreeMap::new(),
+            manage_theme: true,
+            high_contrast_accent: false,
+            gtk_accent: false,
+            animate_accent: false,
+            window_hint_enabled: true,
+            separate_window_hint: false,
+            pick_window_hint: false,
+            auto_accent: false,
+            accent_temperature_bias: None,
+            pinned_hue: None,
+            backend: Backend::default(),
+            wallpaper_scope: WallpaperScope::default(),
+            mode_switch_delay_ms: 150,
+            icon_name: DEFAULT_ICON.to_string(),
+            slideshow_poll_secs: None,
+            pause_resume_hours: None,
+            schedule_enabled: false,
+            schedule_mode: ScheduleMode::default(),
+            dark_start: Time::new(19, 0),
+            light_start: Time::new(7, 0),
+            latitude: 0.0,
+            longitude: 0.0,
+            dark_settings_command: None,
+            light_settings_command: None,
+        }
+    }
+}
+
+/// What the light and dark mode schedule follows.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ScheduleMode {
+    /// The set times.
+    #[default]
+    Manual,
+    /// Sunrise and sunset at the set location, or the set times on days the sun doesn't rise
+    /// or set.
+    Solar,
+}
+
+/// A time of day, in local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Time {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl Time {
+    pub const fn new(hour: u8, minute: u8) -> Self {
+        Self { hour, minute }
+    }
+
+    /// The time the given number of minutes after midnight, wrapping around at midnight.
+    pub fn from_minutes(minutes: u32) -> Self {
+        let minutes = minutes % (24 * 60);
+        Self::new((minutes / 60) as u8, (minutes % 60) as u8)
+    }
+
+    /// The number of minutes since midnight.
+    pub fn minutes(self) -> u32 {
+        u32::from(self.hour) * 60 + u32::from(self.minute)
+    }
+
+    /// The time as a chrono time, with out of range components from a hand-edited config
+    /// clamped.
+    pub fn naive(self) -> NaiveTime {
+        NaiveTime::from_hms_opt(self.hour.min(23).into(), self.minute.min(59).into(), 0)
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+/// How many palettes are kept in the history.
+pub const HISTORY_LEN: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaletteRecord {
+    /// A short description of the wallpaper the palette was extracted from.
+    pub label: String,
+    pub colors: Vec<Srgb>,
+    pub chosen: Option<Srgb>,
 }
 
 impl Config {
     pub fn config() -> Result<cosmic_config::Config, cosmic_config::Error> {
         cosmic_config::Config::new(AppModel::APP_ID, Config::VERSION)
     }
+
+    /// Copies the single `enabled` flag of older configs into the per-mode ones.
+    pub fn migrate(&mut self, handler: &cosmic_config::Config) -> Result<(), cosmic_config::Error> {
+        if handler.get::<bool>("dark_enabled").is_ok()
+            || handler.get::<bool>("light_enabled").is_ok()
+        {
+            return Ok(());
+        }
+        let Ok(enabled) = handler.get::<bool>("enabled") else {
+            return Ok(());
+        };
+        logged_write("dark_enabled, light_enabled", enabled, || {
+            self.set_dark_enabled(handler, enabled)?;
+            self.set_light_enabled(handler, enabled)
+        })?;
+        Ok(())
+    }
+
+    /// Whether the wallpapers of the mode are applied.
+    pub fn enabled(&self, is_dark: bool) -> bool {
+        if is_dark {
+            self.dark_enabled
+        } else {
+            self.light_enabled
+        }
+    }
+
+    /// Whether switching to the mode applies any wallpapers: the mode has to be enabled and have
+    /// wallpapers stored.
+    pub fn is_managing(&self, is_dark: bool) -> bool {
+        self.enabled(is_dark) && !self.entries(is_dark).is_empty()
+    }
+
+    /// Sets whether the wallpapers of the mode are applied.
+    pub fn set_enabled(
+        &mut self,
+        handler: &cosmic_config::Config,
+        is_dark: bool,
+        enabled: bool,
+    ) -> Result<bool, cosmic_config::Error> {
+        let key = if is_dark {
+            "dark_enabled"
+        } else {
+            "light_enabled"
+        };
+        logged_write(key, enabled, || {
+            if is_dark {
+                self.set_dark_enabled(handler, enabled)
+            } else {
+                self.set_light_enabled(handler, enabled)
+            }
+        })
+    }
+
+    /// The command the settings button of the mode launches, if it has one of its own.
+    pub fn settings_command(&self, is_dark: bool) -> Option<&str> {
+        if is_dark {
+            self.dark_settings_command.as_deref()
+        } else {
+            self.light_settings_command.as_deref()
+        }
+    }
+
+    pub fn extraction(&self) -> Extraction {
+        let extraction = Extraction::new(self.quality, self.deterministic_extraction);
+        match self.color_threshold {
+            Some(threshold) => extraction.with_threshold(threshold),
+            None => extraction,
+        }
+    }
+
+    /// The configured icon name, or the default one if the icon theme doesn't have it.
+    pub fn valid_icon_name(&self) -> String {
+        if self.icon_name == DEFAULT_ICON || icon::from_name(&*self.icon_name).path().is_some() {
+            self.icon_name.clone()
+        } else {
+            tracing::warn!(
+                icon_name = self.icon_name,
+                "icon not found, using the default one"
+            );
+            DEFAULT_ICON.to_string()
+        }
+    }
+
+    /// The entries stored for the mode.
+    pub fn entries(&self, is_dark: bool) -> &[Entry] {
+        if is_dark {
+            &self.dark
+        } else {
+            &self.light
+        }
+    }
+
+    /// Whether some wallpaper is shown in both modes.
+    pub fn shares_wallpapers(&self) -> bool {
+        self.dark
+            .iter()
+            .any(|d| self.light.iter().any(|l| l.source == d.source))
+    }
+
+    /// Sets how all the wallpapers of the mode are scaled, and with `link_modes` how the same
+    /// wallpapers are scaled in the other mode.
+    pub fn set_scaling_mode(
+        &mut self,
+        handler: &cosmic_config::Config,
+        is_dark: bool,
+        scaling_mode: ScalingMode,
+    ) -> Result<bool, cosmic_config::Error> {
+        let sources = self
+            .entries(is_dark)
+            .iter()
+            .map(|e| e.source.clone())
+            .collect::<Vec<_>>();
+        let entries = self
+            .entries(is_dark)
+            .iter()
+            .cloned()
+            .map(|mut e| {
+                e.scaling_mode = scaling_mode.clone();
+                e
+            })
+            .collect();
+        let mut changed = self.set_entries(handler, is_dark, entries)?;
+        if self.link_modes {
+            let linked = self
+                .entries(!is_dark)
+                .iter()
+                .cloned()
+                .map(|mut e| {
+                    if sources.contains(&e.source) {
+                        e.scaling_mode = scaling_mode.clone();
+                    }
+                    e
+                })
+                .collect();
+            changed |= self.set_entries(handler, !is_dark, linked)?;
+        }
+        Ok(changed)
+    }
+
+    /// Stores the entries of the mode.
+    pub fn set_entries(
+        &mut self,
+        handler: &cosmic_config::Config,
+        is_dark: bool,
+        entries: Vec<Entry>,
+    ) -> Result<bool, cosmic_config::Error> {
+        let key = if is_dark { "dark" } else { "light" };
+        logged_write(key, summary(&entries), || {
+            if is_dark {
+                self.set_dark(handler, entries)
+            } else {
+                self.set_light(handler, entries)
+            }
+        })
+    }
+
+    /// The stored entries of the mode with the shown ones merged in, each replacing the stored
+    /// entry of its output. Outputs that aren't shown keep their entries for when they're
+    /// connected again. While the outputs share a wallpaper only the default entry is taken, and
+    /// the per-output ones are kept for when they stop sharing it.
+    pub fn merged_entries(&self, is_dark: bool, shown: &Bg) -> Vec<Entry> {
+        let shown = if shown.same_on_all {
+            &shown.entries[..shown.entries.len().min(1)]
+        } else {
+            &shown.entries[..]
+        };
+        let mut entries = self.entries(is_dark).to_vec();
+        for entry in shown {
+            match entries.iter_mut().find(|e| e.output == entry.output) {
+                Some(stored) => *stored = entry.clone(),
+                None => entries.push(entry.clone()),
+            }
+        }
+        entries
+    }
+
+    /// Moves the palette to the front of the history, recording the accent chosen from it.
+    pub fn record_palette(
+        &mut self,
+        handler: &cosmic_config::Config,
+        label: String,
+        colors: Vec<Srgb>,
+        chosen: Srgb,
+    ) -> Result<bool, cosmic_config::Error> {
+        let mut history = self.history.clone();
+        history.retain(|r| r.colors != colors);
+        history.insert(
+            0,
+            PaletteRecord {
+                label,
+                colors,
+                chosen: Some(chosen),
+            },
+        );
+        history.truncate(HISTORY_LEN);
+        self.set_history(handler, history)
+    }
+
+    /// Remembers the accent picked for the image wallpaper.
+    pub fn remember_wallpaper_accent(
+        &mut self,
+        handler: &cosmic_config::Config,
+        path: &Path,
+        accent: Srgb,
+    ) -> Result<bool, cosmic_config::Error> {
+        if self.wallpaper_accents.get(path) == Some(&accent) {
+            return Ok(false);
+        }
+        let mut accents = self.wallpaper_accents.clone();
+        accents.insert(path.to_path_buf(), accent);
+        self.set_wallpaper_accents(handler, accents)
+    }
+
+    /// Writes the default value of every setting, leaving the theme alone. The captured
+    /// wallpapers, the palette history, the output names and the remembered accents are the
+    /// user's data rather than settings, so they're kept.
+    pub fn reset(&mut self, handler: &cosmic_config::Config) -> Result<bool, cosmic_config::Error> {
+        tracing::info!("resetting config to defaults");
+        let Config {
+            dark_enabled,
+            light_enabled,
+            dark: _,
+            light: _,
+            link_modes,
+            enforce_wallpapers,
+            extract_colors,
+            use_bg_color,
+            color_count,
+            shade_count,
+            include_base_color,
+            include_gradient_colors,
+            include_gradient_average,
+            color_metric,
+            color_merge_distance,
+            swatch_sort,
+            min_swatch_luminance,
+            max_swatch_luminance,
+            color_quantize_bits,
+            deterministic_extraction,
+            log_timings,
+            quality,
+            color_threshold,
+            output_names: _,
+            accent_source_output,
+            history: _,
+            remember_accent,
+            wallpaper_accents: _,
+            manage_theme,
+            high_contrast_accent,
+            gtk_accent,
+            animate_accent,
+            window_hint_enabled,
+            separate_window_hint,
+            pick_window_hint,
+            auto_accent,
+            accent_temperature_bias,
+            pinned_hue,
+            backend,
+            wallpaper_scope,
+            mode_switch_delay_ms,
+            icon_name,
+            slideshow_poll_secs,
+            pause_resume_hours,
+            schedule_enabled,
+            schedule_mode,
+            dark_start,
+            light_start,
+            latitude,
+            longitude,
+            dark_settings_command,
+            light_settings_command,
+        } = Config::default();
+        let mut changed = false;
+        changed |= self.set_dark_enabled(handler, dark_enabled)?;
+        changed |= self.set_light_enabled(handler, light_enabled)?;
+        changed |= self.set_link_modes(handler, link_modes)?;
+        changed |= self.set_enforce_wallpapers(handler, enforce_wallpapers)?;
+        changed |= self.set_extract_colors(handler, extract_colors)?;
+        changed |= self.set_use_bg_color(handler, use_bg_color)?;
+        changed |= self.set_color_count(handler, color_count)?;
+        changed |= self.set_shade_count(handler, shade_count)?;
+        changed |= self.set_include_base_color(handler, include_base_color)?;
+        changed |= self.set_include_gradient_colors(handler, include_gradient_colors)?;
+        changed |= self.set_include_gradient_average(handler, include_gradient_average)?;
+        changed |= self.set_color_metric(handler, color_metric)?;
+        changed |= self.set_color_merge_distance(handler, color_merge_distance)?;
+        changed |= self.set_swatch_sort(handler, swatch_sort)?;
+        changed |= self.set_min_swatch_luminance(handler, min_swatch_luminance)?;
+        changed |= self.set_max_swatch_luminance(handler, max_swatch_luminance)?;
+        changed |= self.set_color_quantize_bits(handler, color_quantize_bits)?;
+        changed |= self.set_deterministic_extraction(handler, deterministic_extraction)?;
+        changed |= self.set_log_timings(handler, log_timings)?;
+        changed |= self.set_quality(handler, quality)?;
+        changed |= self.set_color_threshold(handler, color_threshold)?;
+        changed |= self.set_accent_source_output(handler, accent_source_output)?;
+        changed |= self.set_remember_accent(handler, remember_accent)?;
+        changed |= self.set_manage_theme(handler, manage_theme)?;
+        changed |= self.set_high_contrast_accent(handler, high_contrast_accent)?;
+        changed |= self.set_gtk_accent(handler, gtk_accent)?;
+        changed |= self.set_animate_accent(handler, animate_accent)?;
+        changed |= self.set_window_hint_enabled(handler, window_hint_enabled)?;
+        changed |= self.set_separate_window_hint(handler, separate_window_hint)?;
+        changed |= self.set_pick_window_hint(handler, pick_window_hint)?;
+        changed |= self.set_auto_accent(handler, auto_accent)?;
+        changed |= self.set_accent_temperature_bias(handler, accent_temperature_bias)?;
+        changed |= self.set_pinned_hue(handler, pinned_hue)?;
+        changed |= self.set_backend(handler, backend)?;
+        changed |= self.set_wallpaper_scope(handler, wallpaper_scope)?;
+        changed |= self.set_mode_switch_delay_ms(handler, mode_switch_delay_ms)?;
+        changed |= self.set_icon_name(handler, icon_name)?;
+        changed |= self.set_slideshow_poll_secs(handler, slideshow_poll_secs)?;
+        changed |= self.set_pause_resume_hours(handler, pause_resume_hours)?;
+        changed |= self.set_schedule_enabled(handler, schedule_enabled)?;
+        changed |= self.set_schedule_mode(handler, schedule_mode)?;
+        changed |= self.set_dark_start(handler, dark_start)?;
+        changed |= self.set_light_start(handler, light_start)?;
+        changed |= self.set_latitude(handler, latitude)?;
+        changed |= self.set_longitude(handler, longitude)?;
+        changed |= self.set_dark_settings_command(handler, dark_settings_command)?;
+        changed |= self.set_light_settings_command(handler, light_settings_command)?;
+        Ok(changed)
+    }
+
+    /// The times of day the schedule switches to dark and to light mode on the date.
+    pub fn schedule_starts(&self, date: NaiveDate) -> (NaiveTime, NaiveTime) {
+        if self.schedule_mode == ScheduleMode::Solar {
+            let (latitude, longitude) = (self.latitude.into(), self.longitude.into());
+            if let Some((sunrise, sunset)) = solar::sun_times(date, latitude, longitude) {
+                return (
+                    sunset.with_timezone(&Local).time(),
+                    sunrise.with_timezone(&Local).time(),
+                );
+            }
+            tracing::debug!(%date, "no sunrise or sunset, using the set times");
+        }
+        (self.dark_start.naive(), self.light_start.naive())
+    }
+
+    /// Whether the schedule has dark mode at the time.
+    pub fn scheduled_is_dark(&self, now: DateTime<Local>) -> bool {
+        let (dark, light) = self.schedule_starts(now.date_naive());
+        let now = now.time();
+        if dark <= light {
+            dark <= now && now < light
+        } else {
+            // Dark mode lasts over midnight.
+            dark <= now || now < light
+        }
+    }
+
+    /// The first time after `now` the schedule switches modes, skipping a start that falls into
+    /// a daylight saving gap.
+    pub fn next_scheduled_switch(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        let today = now.date_naive();
+        // The starts move from day to day when following the sun.
+        [today, today.succ_opt()?]
+            .into_iter()
+            .flat_map(|date| {
+                let (dark, light) = self.schedule_starts(date);
+                [date.and_time(dark), date.and_time(light)]
+            })
+            .filter(|&start| start > now.naive_local())
+            .filter_map(|start| start.and_local_timezone(Local).earliest())
+            .min()
+    }
+
+    /// The display name of the output, or its raw key if none was captured.
+    pub fn output_label(&self, output: &str) -> String {
+        self.output_names
+            .get(output)
+            .cloned()
+            .unwrap_or_else(|| output.to_string())
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct Bg {
+    /// The default background followed by the per-output ones.
     pub entries: Vec<Entry>,
+    pub same_on_all: bool,
+}
+
+impl From<cosmic_bg_config::Config> for Bg {
+    /// Captures the backgrounds in the order `Backend::apply` writes them back in, so storing
+    /// `entries` for a mode and applying them reproduces the same cosmic-bg state.
+    fn from(mut config: cosmic_bg_config::Config) -> Self {
+        let mut entries = Vec::with_capacity(config.backgrounds.len() + 1);
+        entries.push(config.default_background);
+        entries.append(&mut config.backgrounds);
+        Self {
+            entries,
+            same_on_all: config.same_on_all,
+        }
+    }
+}
+
+impl Bg {
+    /// Reads the backgrounds from the cosmic-bg config of the context, falling back to reading
+    /// the keys one by one.
+    pub fn load(context: &Context) -> Result<Self, (Vec<cosmic_config::Error>, Self)> {
+        match load_bg_config(context) {
+            Ok(config) => Ok(Self::from(config)),
+            Err(why) => {
+                tracing::warn!(%why, "unexpected cosmic-bg config layout, reading it key by key");
+                let (errors, bg) = Self::read_keys(context);
+                Err((std::iter::once(why).chain(errors).collect(), bg))
+            }
+        }
+    }
+
+    /// Writes the entries back to the cosmic-bg config of the context. Whether the outputs share
+    /// a wallpaper is left as cosmic-bg has it.
+    pub fn write_to(&self, context: &Context) -> Result<(), cosmic_config::Error> {
+        let mut config = load_bg_config(context)?;
+        for entry in &self.entries {
+            let key = format!("cosmic-bg/{}", entry.output);
+            logged_write(&key, summary(std::slice::from_ref(entry)), || {
+                config.set_entry(context, entry.clone())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Reads whatever it can of the cosmic-bg config, for when it can't be loaded as a whole, like
+    /// after cosmic-bg changed its layout. Entries that fail to read are left out.
+    fn read_keys(context: &Context) -> (Vec<cosmic_config::Error>, Self) {
+        let mut errors = vec![];
+        let mut read = |key: &str| match context.0.get::<Entry>(key) {
+            Ok(entry) => Some(entry),
+            Err(why) => {
+                tracing::warn!(%why, key, "failed to read cosmic-bg entry");
+                errors.push(why);
+                None
+            }
+        };
+        let mut entries = Vec::from_iter(read("all"));
+        let outputs = context
+            .0
+            .get::<Vec<String>>("backgrounds")
+            .unwrap_or_default();
+        entries.extend(outputs.iter().filter_map(|o| read(&format!("output.{o}"))));
+        let same_on_all = context.0.get::<bool>("same-on-all").unwrap_or(true);
+        (
+            errors,
+            Self {
+                entries,
+                same_on_all,
+            },
+        )
+    }
 }
 
 impl CosmicConfigEntry for Bg {
     const VERSION: u64 = 1;
 
+    /// Writes the entries back to cosmic-bg, as `write_to` does.
     fn write_entry(&self, _config: &cosmic_config::Config) -> Result<(), cosmic_config::Error> {
-        Ok(())
+        self.write_to(&context()?)
     }
 
     fn get_entry(
         _config: &cosmic_config::Config,
     ) -> Result<Self, (Vec<cosmic_config::Error>, Self)> {
-        let context = context().unwrap();
-        let mut config = cosmic_bg_config::Config::load(&context).unwrap();
-        let mut entries = Vec::with_capacity(config.backgrounds.len() + 1);
-        entries.push(config.default_background);
-        entries.append(&mut config.backgrounds);
-        Ok(Self { entries })
+        let context = context().map_err(|why| (vec![why], Self::default()))?;
+        Self::load(&context)
     }
 
     fn update_keys<T: AsRef<str>>(
@@ -55,10 +746,225 @@ impl CosmicConfigEntry for Bg {
             .map(|k| k.as_ref())
             .any(|k| k == "all" || k.starts_with("output"))
         {
-            *self = Bg::get_entry(config).unwrap();
-            (vec![], vec![""])
+            match Bg::get_entry(config) {
+                Ok(bg) => {
+                    *self = bg;
+                    (vec![], vec![""])
+                }
+                Err((errors, bg)) => {
+                    *self = bg;
+                    (errors, vec![""])
+                }
+            }
         } else {
             (vec![], vec![])
         }
     }
 }
+
+/// Logs a config write along with a summary of the new value, then performs it.
+pub fn logged_write<T>(
+    key: &str,
+    value: impl Debug,
+    write: impl FnOnce() -> Result<T, cosmic_config::Error>,
+) -> Result<T, cosmic_config::Error> {
+    tracing::info!(key, ?value, "writing config");
+    write()
+}
+
+/// A short description of each entry, for logging.
+pub fn summary(entries: &[Entry]) -> Vec<String> {
+    entries
+        .iter()
+        .map(|e| match &e.source {
+            Source::Path(path) => format!("{}: {}", e.output, path.display()),
+            Source::Color(color) => format!("{}: {color:?}", e.output),
+        })
+        .collect()
+}
+
+/// How many times loading the cosmic-bg config is attempted before giving up.
+const LOAD_ATTEMPTS: u32 = 5;
+
+/// The cosmic-bg config context, logging why if it can't be opened.
+#[cfg(not(test))]
+pub fn bg_context() -> Option<Context> {
+    context()
+        .inspect_err(|why| tracing::error!(%why, "failed to open the cosmic-bg config"))
+        .ok()
+}
+
+/// The context the running test set up with `use_bg_context`, so tests never touch the config
+/// of the desktop they run on.
+#[cfg(test)]
+pub fn bg_context() -> Option<Context> {
+    TEST_BG_CONTEXT.with_borrow(Clone::clone)
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The context `bg_context` returns to the test running on this thread.
+    static TEST_BG_CONTEXT: std::cell::RefCell<Option<Context>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// A cosmic-bg config context in a temporary directory, removed when the guard is dropped.
+#[cfg(test)]
+pub(crate) fn temp_bg_context() -> (tempfile::TempDir, Context) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().to_path_buf();
+    let config =
+        cosmic_config::Config::with_custom_path("com.system76.CosmicBackground", 1, path).unwrap();
+    (dir, Context(config))
+}
+
+/// Makes `bg_context` return the context for the rest of the test.
+#[cfg(test)]
+pub(crate) fn use_bg_context(context: &Context) {
+    TEST_BG_CONTEXT.set(Some(context.clone()));
+}
+
+/// A config handler writing into a temporary directory, removed when the guard is dropped.
+#[cfg(test)]
+pub(crate) fn temp_handler() -> (tempfile::TempDir, cosmic_config::Config) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().to_path_buf();
+    let handler =
+        cosmic_config::Config::with_custom_path(AppModel::APP_ID, Config::VERSION, path).unwrap();
+    (dir, handler)
+}
+
+/// Loads the cosmic-bg config. It's only tried once, since this runs on the applet's update
+/// path, which must never block; `wait_for_bg_config` retries at startup.
+pub fn load_bg_config(context: &Context) -> Result<cosmic_bg_config::Config, cosmic_config::Error> {
+    cosmic_bg_config::Config::load(context).inspect_err(|why| {
+        tracing::error!(%why, "failed to load cosmic-bg config");
+    })
+}
+
+/// Loads the cosmic-bg config, retrying with an exponential backoff since it may not be
+/// available yet while the compositor is starting. None if it never became available.
+pub async fn wait_for_bg_config() -> Option<cosmic_bg_config::Config> {
+    let mut delay = Duration::from_millis(100);
+    for attempt in 1..=LOAD_ATTEMPTS {
+        match context().and_then(|context| cosmic_bg_config::Config::load(&context)) {
+            Ok(config) => return Some(config),
+            Err(why) if attempt < LOAD_ATTEMPTS => {
+                tracing::warn!(%why, attempt, "failed to load cosmic-bg config, retrying");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            Err(why) => tracing::error!(%why, "failed to load cosmic-bg config"),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_keeps_user_data() {
+        let (_dir, handler) = temp_handler();
+        let entry = Entry::new("all".to_string(), Source::Path(PathBuf::from("/tmp/a.png")));
+        let kept = Config {
+            dark: vec![entry.clone()],
+            light: vec![entry],
+            output_names: BTreeMap::from([("DP-1".to_string(), "Desk".to_string())]),
+            wallpaper_accents: BTreeMap::from([(
+                PathBuf::from("/tmp/a.png"),
+                Srgb::new(0.2, 0.4, 0.6),
+            )]),
+            ..Config::default()
+        };
+        let mut config = Config {
+            dark_enabled: true,
+            color_count: 3,
+            link_modes: true,
+            icon_name: "other-icon".to_string(),
+            ..kept.clone()
+        };
+        assert_eq!(config.reset(&handler).ok(), Some(true));
+        assert_eq!(config, kept);
+        assert_eq!(config.reset(&handler).ok(), Some(false));
+    }
+
+    #[test]
+    fn bg_round_trip() {
+        let (_dir, context) = temp_bg_context();
+        let bg = Bg {
+            entries: vec![
+                Entry::new("all".to_string(), Source::Path(PathBuf::from("/tmp/a.png"))),
+                Entry::new(
+                    "DP-1".to_string(),
+                    Source::Color(cosmic_bg_config::Color::Single([0.2, 0.4, 0.6])),
+                ),
+            ],
+            same_on_all: false,
+        };
+        bg.write_to(&context).unwrap();
+        let read = Bg::load(&context).unwrap();
+        assert_eq!(read.entries, bg.entries);
+    }
+
+    #[test]
+    fn read_keys_skips_entries_in_a_newer_layout() {
+        use cosmic_config::ConfigSet;
+
+        let (_dir, context) = temp_bg_context();
+        let all = Entry::new("all".to_string(), Source::Path(PathBuf::from("/tmp/a.png")));
+        let hdmi = Entry::new(
+            "HDMI-1".to_string(),
+            Source::Path(PathBuf::from("/tmp/b.png")),
+        );
+        context.0.set("all", &all).unwrap();
+        context.0.set("output.HDMI-1", &hdmi).unwrap();
+        // A layout cosmic-bg might move to, which this version can't read as an entry.
+        context
+            .0
+            .set(
+                "output.DP-1",
+                BTreeMap::from([("layers", vec!["/tmp/c.png"])]),
+            )
+            .unwrap();
+        context
+            .0
+            .set(
+                "backgrounds",
+                vec!["DP-1".to_string(), "HDMI-1".to_string()],
+            )
+            .unwrap();
+        context.0.set("same-on-all", false).unwrap();
+        let (errors, bg) = Bg::read_keys(&context);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(bg.entries, [all, hdmi]);
+        assert!(!bg.same_on_all);
+    }
+
+    #[test]
+    fn is_managing_needs_the_mode_enabled_and_entries() {
+        let entry = Entry::new("all".to_string(), Source::Path(PathBuf::from("/tmp/a.png")));
+        for (enabled, entries, managing) in [
+            (false, vec![], false),
+            (true, vec![], false),
+            (false, vec![entry.clone()], false),
+            (true, vec![entry.clone()], true),
+        ] {
+            let dark = Config {
+                dark_enabled: enabled,
+                dark: entries.clone(),
+                ..Config::default()
+            };
+            assert_eq!(dark.is_managing(true), managing);
+            assert!(!dark.is_managing(false));
+            let light = Config {
+                light_enabled: enabled,
+                light: entries,
+                ..Config::default()
+            };
+            assert_eq!(light.is_managing(false), managing);
+            assert!(!light.is_managing(true));
+        }
+    }
+}
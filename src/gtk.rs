@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Mirrors the accent color into the GNOME settings GTK applications read, so that legacy
+//! applications match the COSMIC theme.
+
+use cosmic::cosmic_theme::palette::{FromColor, Hsl, Srgb};
+use cosmic::iced::Color;
+use tokio::process::Command;
+
+/// The named accent colors `org.gnome.desktop.interface accent-color` accepts, with their hues.
+const ACCENTS: [(&str, f32); 8] = [
+    ("red", 353.0),
+    ("orange", 23.0),
+    ("yellow", 41.0),
+    ("green", 131.0),
+    ("teal", 189.0),
+    ("blue", 213.0),
+    ("purple", 285.0),
+    ("pink", 331.0),
+];
+
+/// Colors less saturated than this map to the gray `slate` accent.
+const MIN_SATURATION: f32 = 0.15;
+
+/// The GNOME accent color closest to the given color.
+pub fn named_accent(color: Color) -> &'static str {
+    let hsl = Hsl::from_color(Srgb::from(color));
+    if hsl.saturation < MIN_SATURATION {
+        return "slate";
+    }
+    let hue = hsl.hue.into_positive_degrees();
+    let distance = |h: f32| {
+        let diff = (h - hue).abs();
+        diff.min(360.0 - diff)
+    };
+    ACCENTS
+        .iter()
+        .min_by(|(_, l), (_, r)| distance(*l).total_cmp(&distance(*r)))
+        .map(|(name, _)| *name)
+        .unwrap_or("blue")
+}
+
+/// Sets the GNOME accent color in the background. Failures are only logged, since not having
+/// the gsettings schema installed just means there are no GTK applications to follow along.
+pub fn set_accent(color: Color) {
+    let name = named_accent(color);
+    tokio::spawn(async move {
+        let status = Command::new("gsettings")
+            .args(["set", "org.gnome.desktop.interface", "accent-color", name])
+            .status()
+            .await;
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => tracing::warn!(%status, "failed to set the GTK accent color"),
+            Err(why) => tracing::warn!(%why, "failed to run gsettings"),
+        }
+    });
+}
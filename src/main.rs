@@ -1,11 +1,32 @@
 // SPDX-License-Identifier: GPL-3.0
 
 mod app;
+mod backend;
+mod cache;
+mod cli;
+mod colors;
 mod config;
+mod gtk;
 mod i18n;
+mod snapshot;
+mod solar;
 mod unique;
 
-fn main() -> cosmic::iced::Result {
+use std::process::ExitCode;
+
+use tracing_subscriber::EnvFilter;
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+        )
+        .init();
+
+    if let Some(code) = cli::run(std::env::args().skip(1)) {
+        return code;
+    }
+
     // Get the system's preferred languages.
     let requested_languages = i18n_embed::DesktopLanguageRequester::requested_languages();
 
@@ -13,5 +34,11 @@ fn main() -> cosmic::iced::Result {
     i18n::init(&requested_languages);
 
     // Starts the applet's event loop with `()` as the application's flags.
-    cosmic::applet::run::<app::AppModel>(())
+    match cosmic::applet::run::<app::AppModel>(()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(why) => {
+            tracing::error!(%why, "applet failed");
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,50 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Snapshots of a mode's look, its wallpapers and accent, in a file that can be shared.
+
+use cosmic::cosmic_theme::palette::Srgb;
+use cosmic_bg_config::Entry;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+/// The version of the snapshot format, bumped whenever it changes incompatibly.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// Whether the look is the one of dark mode.
+    pub is_dark: bool,
+    pub entries: Vec<Entry>,
+    pub accent: Option<Srgb>,
+}
+
+/// Just the version of a snapshot, which every version of the format starts with.
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+impl Snapshot {
+    pub fn new(is_dark: bool, entries: Vec<Entry>, accent: Option<Srgb>) -> Self {
+        Self {
+            version: VERSION,
+            is_dark,
+            entries,
+            accent,
+        }
+    }
+
+    pub fn to_ron(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, PrettyConfig::default()).map_err(|why| why.to_string())
+    }
+
+    /// Parses a snapshot, rejecting ones of another version.
+    pub fn from_ron(snapshot: &str) -> Result<Self, String> {
+        let header: Header = ron::from_str(snapshot).map_err(|why| why.to_string())?;
+        if header.version != VERSION {
+            return Err(format!("unsupported snapshot version {}", header.version));
+        }
+        ron::from_str(snapshot).map_err(|why| why.to_string())
+    }
+}
@@ -0,0 +1,85 @@
+// SPDX-License-Identifier: GPL-3.0
+
+//! Sunrise and sunset times, from the sunrise equation. They're accurate to a minute or two,
+//! plenty for switching the theme mode.
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// The Julian day of the J2000 epoch, noon of the first of January 2000.
+const J2000: f64 = 2_451_545.0;
+/// The Julian day of the Unix epoch.
+const UNIX_EPOCH: f64 = 2_440_587.5;
+/// The tilt of the Earth's axis, in degrees.
+const OBLIQUITY: f64 = 23.4397;
+/// The solar elevation at sunrise and sunset, in degrees. Refraction and the size of the sun's
+/// disc make it appear above the horizon while its center is still below.
+const HORIZON: f64 = -0.833;
+
+/// The sunrise and sunset on the day at the location, with the latitude in degrees north and the
+/// longitude in degrees east. None if the sun doesn't rise or doesn't set that day.
+pub fn sun_times(
+    date: NaiveDate,
+    latitude: f64,
+    longitude: f64,
+) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let epoch = NaiveDate::from_ymd_opt(2000, 1, 1)?;
+    // The mean solar noon at the longitude, in days since J2000.
+    let noon = (date - epoch).num_days() as f64 - longitude / 360.0;
+    let anomaly = (357.5291 + 0.985_600_28 * noon)
+        .rem_euclid(360.0)
+        .to_radians();
+    let center =
+        1.9148 * anomaly.sin() + 0.0200 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+    let ecliptic_longitude = (anomaly.to_degrees() + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    let transit = J2000 + noon + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * ecliptic_longitude).sin();
+    let declination = (ecliptic_longitude.sin() * OBLIQUITY.to_radians().sin()).asin();
+    let latitude = latitude.to_radians();
+    let cos_hour_angle = (HORIZON.to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+    // Past 1 the sun stays below the horizon all day, below -1 above it.
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees() / 360.0;
+    Some((
+        from_julian(transit - hour_angle)?,
+        from_julian(transit + hour_angle)?,
+    ))
+}
+
+fn from_julian(day: f64) -> Option<DateTime<Utc>> {
+    let secs = (day - UNIX_EPOCH) * 86_400.0;
+    DateTime::from_timestamp(secs as i64, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+
+    fn assert_near(time: DateTime<Utc>, expected: &str) {
+        let expected = NaiveDateTime::parse_from_str(expected, "%Y-%m-%d %H:%M")
+            .unwrap()
+            .and_utc();
+        let off = (time - expected).num_seconds().abs();
+        assert!(off <= 180, "{time} is {off}s off {expected}");
+    }
+
+    #[test]
+    fn berlin_midsummer() {
+        let date = NaiveDate::from_ymd_opt(2026, 6, 21).unwrap();
+        let (sunrise, sunset) = sun_times(date, 52.5, 13.4).unwrap();
+        assert_near(sunrise, "2026-06-21 02:43");
+        assert_near(sunset, "2026-06-21 19:33");
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        let june = NaiveDate::from_ymd_opt(2026, 6, 21).unwrap();
+        let december = NaiveDate::from_ymd_opt(2026, 12, 21).unwrap();
+        assert_eq!(sun_times(june, 80.0, 15.0), None);
+        assert_eq!(sun_times(december, 80.0, 15.0), None);
+    }
+}
@@ -9,6 +9,51 @@ pub trait UniqueIterator: Iterator {
     {
         FromUniqueIterator::from_unique_iter(self)
     }
+
+    fn collect_unique_by<F>(self, mut same: F) -> Vec<Self::Item>
+    where
+        Self: Sized,
+        F: FnMut(&Self::Item, &Self::Item) -> bool,
+    {
+        let mut vec = Vec::new();
+        self.for_each(|i| {
+            if !vec.iter().any(|v| same(v, &i)) {
+                vec.push(i)
+            }
+        });
+        vec
+    }
+
+    fn collect_counted(self) -> Vec<(Self::Item, usize)>
+    where
+        Self: Sized,
+        Self::Item: PartialEq,
+    {
+        let mut vec: Vec<(Self::Item, usize)> = Vec::new();
+        self.for_each(|i| match vec.iter_mut().find(|(v, _)| *v == i) {
+            Some((_, count)) => *count += 1,
+            None => vec.push((i, 1)),
+        });
+        vec
+    }
+
+    fn collect_unique_by_key<K, F>(self, mut key: F) -> Vec<Self::Item>
+    where
+        Self: Sized,
+        K: PartialEq,
+        F: FnMut(&Self::Item) -> K,
+    {
+        let mut keys = Vec::new();
+        let mut vec = Vec::new();
+        self.for_each(|i| {
+            let k = key(&i);
+            if !keys.contains(&k) {
+                keys.push(k);
+                vec.push(i)
+            }
+        });
+        vec
+    }
 }
 
 impl<I: Iterator> UniqueIterator for I {}